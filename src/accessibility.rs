@@ -0,0 +1,100 @@
+// デスクトップのアクセシビリティ設定（アニメーション削減）の取得
+// xdg-desktop-portalのSettingsインターフェース経由で読み取る
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use dbus::arg::{RefArg, Variant};
+use dbus::blocking::Connection as DbusConnection;
+use dbus::message::MatchRule;
+
+use crate::config::ReducedMotion;
+
+const PORTAL_DEST: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const SETTINGS_INTERFACE: &str = "org.freedesktop.portal.Settings";
+
+// reduced-motion: 0 = 指定なし, 1 = 減らす
+const APPEARANCE_NAMESPACE: &str = "org.freedesktop.appearance";
+const APPEARANCE_KEY: &str = "reduced-motion";
+
+// GNOME: enable-animations = false で減らす
+const GNOME_NAMESPACE: &str = "org.gnome.desktop.interface";
+const GNOME_KEY: &str = "enable-animations";
+
+/// 設定値からアニメーション削減フラグを作成
+///
+/// `Auto`の場合はポータルから初期値を読み取り、変更シグナルを購読する
+pub fn reduced_motion_flag(
+    mode: ReducedMotion,
+    dbus_conn: &DbusConnection,
+) -> Result<Arc<AtomicBool>> {
+    let flag = Arc::new(AtomicBool::new(mode == ReducedMotion::On));
+    if mode != ReducedMotion::Auto {
+        return Ok(flag);
+    }
+
+    let initial = prefers_reduced_motion(dbus_conn).unwrap_or(false);
+    info!("アニメーション削減（デスクトップ設定）: {}", "Reduced motion (desktop setting): {}", initial);
+    flag.store(initial, Ordering::Relaxed);
+
+    let rule = MatchRule::new_signal(SETTINGS_INTERFACE, "SettingChanged");
+    let flag_clone = Arc::clone(&flag);
+    dbus_conn.add_match(
+        rule,
+        move |(namespace, key, value): (String, String, Variant<Box<dyn RefArg>>), _, _| {
+            if let Some(reduced) = interpret_setting(&namespace, &key, &value) {
+                info!("アニメーション削減設定の変更: {}", "Reduced motion setting changed: {}", reduced);
+                flag_clone.store(reduced, Ordering::Relaxed);
+            }
+            true
+        },
+    ).context("ポータル設定のマッチルール追加に失敗")?;
+
+    Ok(flag)
+}
+
+/// ポータルから現在のアニメーション削減設定を取得（どちらかが「減らす」なら有効）
+fn prefers_reduced_motion(dbus_conn: &DbusConnection) -> Option<bool> {
+    let values: Vec<bool> = [(APPEARANCE_NAMESPACE, APPEARANCE_KEY), (GNOME_NAMESPACE, GNOME_KEY)]
+        .iter()
+        .filter_map(|(namespace, key)| {
+            let value = read_setting(dbus_conn, namespace, key)?;
+            interpret_setting(namespace, key, &value)
+        })
+        .collect();
+
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.contains(&true))
+    }
+}
+
+/// ポータルの設定値を1件読み取る（ReadOneが無い古いポータルではReadにフォールバック）
+fn read_setting(
+    dbus_conn: &DbusConnection,
+    namespace: &str,
+    key: &str,
+) -> Option<Variant<Box<dyn RefArg>>> {
+    let proxy = dbus_conn.with_proxy(PORTAL_DEST, PORTAL_PATH, Duration::from_millis(500));
+
+    proxy
+        .method_call(SETTINGS_INTERFACE, "ReadOne", (namespace, key))
+        .or_else(|_| proxy.method_call(SETTINGS_INTERFACE, "Read", (namespace, key)))
+        .map(|(value,): (Variant<Box<dyn RefArg>>,)| value)
+        .ok()
+}
+
+/// 設定値をアニメーション削減フラグに変換（対象外のキーはNone）
+fn interpret_setting(namespace: &str, key: &str, value: &Variant<Box<dyn RefArg>>) -> Option<bool> {
+    // Readの戻り値はVariantが二重になっているが、as_u64は中身まで辿る
+    let value = value.as_u64()?;
+    match (namespace, key) {
+        (APPEARANCE_NAMESPACE, APPEARANCE_KEY) => Some(value == 1),
+        (GNOME_NAMESPACE, GNOME_KEY) => Some(value == 0),
+        _ => None,
+    }
+}
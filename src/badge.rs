@@ -0,0 +1,220 @@
+// 常時表示バッジ（出力の隅に現在の入力メソッドを表示し続ける）
+
+use anyhow::Result;
+use wayland_client::protocol::{wl_buffer, wl_surface};
+use wayland_protocols_wlr::layer_shell::v1::client::{
+    zwlr_layer_shell_v1,
+    zwlr_layer_surface_v1::{KeyboardInteractivity, ZwlrLayerSurfaceV1},
+};
+
+use crate::buffer::BufferCache;
+use crate::compositor::WindowTracker;
+use crate::config::{Config, OutputSelection};
+use crate::placement::Placement;
+use crate::render::RenderStyle;
+use crate::wayland::{create_buffer_from_pixels, AppState, OutputTarget, WaylandContext};
+
+/// 常時表示バッジ（長寿命のレイヤーサーフェス）
+pub struct Badge {
+    surface: wl_surface::WlSurface,
+    layer_surface: ZwlrLayerSurfaceV1,
+    /// 通常サイズのバッファキャッシュ
+    cache: BufferCache,
+    /// 切り替え時の拡大表示用バッファキャッシュ（拡大しない設定ではNone）
+    enlarged_cache: Option<BufferCache>,
+    /// 現在アタッチしているバッファ
+    buffer: Option<wl_buffer::WlBuffer>,
+    /// 出力のバッファスケール（バッファはサーフェスサイズのこの倍で描画）
+    scale: i32,
+    /// 現在のサーフェスサイズ（論理ピクセル）
+    size: (u32, u32),
+    /// 表示中のテキスト（隠している間も更新する）
+    text: Option<String>,
+    /// 隠している（バッファを外してマップを解除している）
+    hidden: bool,
+}
+
+impl Badge {
+    /// バッジ用のレイヤーサーフェスを作成（内容は最初のshowで描画）
+    ///
+    /// 置く出力は`placement.outputs`の最初の出力（Focusedの場合はフォーカス中のモニタ）で、
+    /// その出力のバッファスケールで描画する
+    pub fn new(wayland: &mut WaylandContext, config: &Config, windows: &WindowTracker) -> Result<Self> {
+        let badge = &config.indicator.badge;
+        let target = target_output(wayland, config, windows)?;
+        let scale = target.scale;
+
+        let cache = BufferCache::new(
+            badge.width as i32 * scale,
+            badge.height as i32 * scale,
+            RenderStyle::badge(badge, &config.overlay, f64::from(scale)),
+        );
+        let enlarged_cache = (badge.enlarge_scale > 1.0).then(|| {
+            let (width, height) = scaled_size(badge.width, badge.height, badge.enlarge_scale);
+            BufferCache::new(
+                width as i32 * scale,
+                height as i32 * scale,
+                RenderStyle::badge(badge, &config.overlay, badge.enlarge_scale * f64::from(scale)),
+            )
+        });
+
+        // フルスクリーンのウィンドウを覆わないようTopレイヤーに置く
+        let surface = wayland.compositor.create_surface(&wayland.qh, ());
+        let layer_surface = wayland.layer_shell.get_layer_surface(
+            &surface,
+            target.output.as_ref(),
+            zwlr_layer_shell_v1::Layer::Top,
+            "modal_ime_indicator_badge".to_string(),
+            &wayland.qh,
+            (),
+        );
+
+        let placement = Placement::output_edge(badge.anchor, badge.margin, 0, 0);
+        let (margin_top, margin_right, margin_bottom, margin_left) = placement.margin;
+        layer_surface.set_anchor(placement.anchor);
+        layer_surface.set_margin(margin_top, margin_right, margin_bottom, margin_left);
+        layer_surface.set_size(badge.width, badge.height);
+        layer_surface.set_keyboard_interactivity(KeyboardInteractivity::None);
+        layer_surface.set_exclusive_zone(-1);
+
+        // 入力リージョンを空に設定
+        let region = wayland.compositor.create_region(&wayland.qh, ());
+        surface.set_input_region(Some(&region));
+        surface.set_buffer_scale(scale);
+
+        surface.commit();
+        // リージョンはコミット時にコピーされるので破棄してよい
+        region.destroy();
+
+        // configure待機
+        let mut state = AppState::new();
+        wayland.event_queue.roundtrip(&mut state)?;
+
+        Ok(Self {
+            surface,
+            layer_surface,
+            cache,
+            enlarged_cache,
+            buffer: None,
+            scale,
+            size: (badge.width, badge.height),
+            text: None,
+            hidden: false,
+        })
+    }
+
+    /// 表示中のテキストがあるかどうか（初回表示の判定用、隠している間はfalse）
+    pub fn is_showing(&self) -> bool {
+        self.text.is_some() && !self.hidden
+    }
+
+    /// 拡大表示が可能かどうか
+    pub fn can_enlarge(&self) -> bool {
+        self.enlarged_cache.is_some()
+    }
+
+    /// テキストを表示（enlargedで拡大表示、隠している間はテキストだけ更新）
+    pub fn show(&mut self, wayland: &mut WaylandContext, text: &str, enlarged: bool) -> Result<()> {
+        if self.hidden {
+            self.text = Some(text.to_string());
+            return Ok(());
+        }
+
+        let cache = match (&mut self.enlarged_cache, enlarged) {
+            (Some(enlarged_cache), true) => enlarged_cache,
+            _ => &mut self.cache,
+        };
+        cache.prerender(text)?;
+
+        let (width, height) = (cache.width(), cache.height());
+        let Some(pixels) = cache.get(text, 1.0) else {
+            return Ok(());
+        };
+
+        // サイズが変わる場合はconfigureを待ってから描画
+        let size = ((width / self.scale) as u32, (height / self.scale) as u32);
+        if self.size != size {
+            self.size = size;
+            self.layer_surface.set_size(size.0, size.1);
+            self.surface.commit();
+            let mut state = AppState::new();
+            wayland.event_queue.roundtrip(&mut state)?;
+        }
+
+        let buffer = create_buffer_from_pixels(&wayland.shm, &wayland.qh, width, height, &pixels)?;
+        self.surface.attach(Some(&buffer), 0, 0);
+        self.surface.damage_buffer(0, 0, width, height);
+        self.surface.commit();
+        wayland.conn.flush()?;
+
+        // 置き換えたバッファは不要になったので破棄
+        if let Some(previous) = self.buffer.replace(buffer) {
+            previous.destroy();
+        }
+        self.text = Some(text.to_string());
+
+        Ok(())
+    }
+
+    /// サーフェスとバッファを破棄（設定の再読み込み時）
+    pub fn destroy(self) {
+        self.layer_surface.destroy();
+        self.surface.destroy();
+        if let Some(buffer) = self.buffer {
+            buffer.destroy();
+        }
+    }
+
+    /// 表示中のテキストを通常サイズで再表示（拡大表示からの復帰）
+    pub fn restore(&mut self, wayland: &mut WaylandContext) -> Result<()> {
+        match self.text.clone() {
+            Some(text) => self.show(wayland, &text, false),
+            None => Ok(()),
+        }
+    }
+
+    /// 隠す・再表示する（再表示では隠している間に更新されたテキストを表示）
+    pub fn set_hidden(&mut self, wayland: &mut WaylandContext, hidden: bool) -> Result<()> {
+        if self.hidden == hidden {
+            return Ok(());
+        }
+        self.hidden = hidden;
+
+        if hidden {
+            // バッファを外すとレイヤーサーフェスのマップが解除される
+            self.surface.attach(None, 0, 0);
+            self.surface.commit();
+            wayland.conn.flush()?;
+            if let Some(buffer) = self.buffer.take() {
+                buffer.destroy();
+            }
+            return Ok(());
+        }
+
+        // マップし直すには、バッファなしでコミットしてconfigureを待つ必要がある
+        self.surface.commit();
+        let mut state = AppState::new();
+        wayland.event_queue.roundtrip(&mut state)?;
+        self.restore(wayland)
+    }
+}
+
+/// バッジを置く出力（Focusedの場合はコンポジタから取得したフォーカス中のモニタ）
+fn target_output(wayland: &mut WaylandContext, config: &Config, windows: &WindowTracker) -> Result<OutputTarget> {
+    wayland.sync_outputs()?;
+    let focused = windows.monitors().into_iter().find(|monitor| monitor.focused);
+    let selection = match (&config.placement.outputs, focused) {
+        (OutputSelection::Focused, Some(monitor)) => OutputSelection::Named(vec![monitor.name]),
+        (selection, _) => selection.clone(),
+    };
+    // 該当する出力が無い場合もコンポジタに任せる出力が1つ返る
+    Ok(wayland.target_outputs(&selection).swap_remove(0))
+}
+
+/// 拡大後のサイズ
+fn scaled_size(width: u32, height: u32, scale: f64) -> (u32, u32) {
+    (
+        (f64::from(width) * scale).round() as u32,
+        (f64::from(height) * scale).round() as u32,
+    )
+}
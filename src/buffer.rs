@@ -0,0 +1,94 @@
+// 描画済みピクセルデータのキャッシュ
+// 表示テキストごとに1回だけ描画し、フェードアウト中はアルファ値を掛けたコピーを返す
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::render::{render_text_to_pixels, RenderStyle};
+
+/// キャッシュされたバッファ（各アルファ値のピクセルデータを保持）
+struct CachedBuffer {
+    /// アルファ=1.0のピクセルデータ（ARGB8888）
+    pixels_full: Vec<u8>,
+}
+
+impl CachedBuffer {
+    /// 指定アルファ値でピクセルデータを生成
+    fn get_pixels_with_alpha(&self, alpha: f64) -> Vec<u8> {
+        if alpha >= 1.0 {
+            return self.pixels_full.clone();
+        }
+
+        let mut pixels = self.pixels_full.clone();
+        // ARGB8888フォーマット: 各ピクセル4バイト [B, G, R, A]
+        for chunk in pixels.chunks_exact_mut(4) {
+            // Cairoは事前乗算アルファを使用するため、全チャンネルにアルファを適用
+            chunk[0] = (chunk[0] as f64 * alpha) as u8; // B
+            chunk[1] = (chunk[1] as f64 * alpha) as u8; // G
+            chunk[2] = (chunk[2] as f64 * alpha) as u8; // R
+            chunk[3] = (chunk[3] as f64 * alpha) as u8; // A
+        }
+        pixels
+    }
+}
+
+/// バッファキャッシュ（テキストごとにCachedBufferを保持）
+///
+/// ```no_run
+/// use wayland_fcitx5_indicator::{BufferCache, Config, RenderStyle};
+///
+/// let config = Config::load();
+/// let mut cache = BufferCache::new(200, 100, RenderStyle::overlay(&config.overlay, 1));
+/// cache.prerender("あ")?;
+/// // 半透明（アルファ0.5）のARGB8888ピクセルデータ
+/// let pixels = cache.get("あ", 0.5).unwrap();
+/// assert_eq!(pixels.len(), 200 * 100 * 4);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct BufferCache {
+    cache: HashMap<String, CachedBuffer>,
+    width: i32,
+    height: i32,
+    style: RenderStyle,
+}
+
+impl BufferCache {
+    /// 描画サイズ（ピクセル）とスタイルを指定して空のキャッシュを作成
+    pub fn new(width: i32, height: i32, style: RenderStyle) -> Self {
+        Self {
+            cache: HashMap::new(),
+            width,
+            height,
+            style,
+        }
+    }
+
+    /// テキストのバッファを事前レンダリング
+    pub fn prerender(&mut self, text: &str) -> Result<()> {
+        if self.cache.contains_key(text) {
+            return Ok(());
+        }
+
+        let pixels = render_text_to_pixels(self.width, self.height, text, 1.0, &self.style)?;
+        self.cache.insert(text.to_string(), CachedBuffer {
+            pixels_full: pixels,
+        });
+        Ok(())
+    }
+
+    /// キャッシュからピクセルデータを取得（未描画のテキストはNone）
+    pub fn get(&self, text: &str, alpha: f64) -> Option<Vec<u8>> {
+        self.cache.get(text).map(|buf| buf.get_pixels_with_alpha(alpha))
+    }
+
+    /// 描画幅（ピクセル）
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    /// 描画高さ（ピクセル）
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+}
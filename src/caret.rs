@@ -0,0 +1,169 @@
+// fcitx5の入力コンテキストのカーソル位置（キャレット）を追跡
+// アプリがfcitx5へ送るSetCursorRect/SetCursorRectV2をDBusモニタで観測する
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use dbus::blocking::Connection as DbusConnection;
+use dbus::channel::MatchingReceiver;
+use dbus::message::{MatchRule, MessageType};
+use dbus::Message;
+
+const INPUT_CONTEXT_INTERFACE: &str = "org.fcitx.Fcitx.InputContext1";
+
+// fcitx5のCapabilityFlag::RelativeRect（座標がウィンドウ相対）
+const CAPABILITY_RELATIVE_RECT: u64 = 1 << 24;
+
+/// キャレットの矩形（論理ピクセル）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaretRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    /// 座標がウィンドウ相対かどうか（Waylandネイティブのアプリ）
+    pub relative: bool,
+}
+
+/// 入力コンテキストごとの状態
+#[derive(Default)]
+struct InputContextState {
+    rect: Option<(i32, i32, i32, i32)>,
+    capability: u64,
+}
+
+/// 監視スレッドと共有するキャレット状態
+#[derive(Default)]
+struct TrackerState {
+    contexts: HashMap<String, InputContextState>,
+    focused: Option<String>,
+}
+
+impl TrackerState {
+    /// フォーカス中の入力コンテキストのキャレット
+    fn current(&self) -> Option<CaretRect> {
+        let context = self.contexts.get(self.focused.as_ref()?)?;
+        let (x, y, width, height) = context.rect?;
+        Some(CaretRect {
+            x,
+            y,
+            width,
+            height,
+            relative: context.capability & CAPABILITY_RELATIVE_RECT != 0,
+        })
+    }
+
+    /// 入力コンテキストへのメソッド呼び出しを反映
+    fn handle(&mut self, msg: &Message) {
+        let (Some(path), Some(member)) = (msg.path(), msg.member()) else {
+            return;
+        };
+        let path = path.to_string();
+
+        match &*member {
+            "FocusIn" => {
+                self.focused = Some(path);
+            }
+            "FocusOut" if self.focused.as_deref() == Some(path.as_str()) => {
+                self.focused = None;
+            }
+            "DestroyIC" => {
+                self.contexts.remove(&path);
+                if self.focused.as_deref() == Some(path.as_str()) {
+                    self.focused = None;
+                }
+            }
+            "SetCapability" => {
+                if let Ok(capability) = msg.read1::<u64>() {
+                    self.contexts.entry(path).or_default().capability = capability;
+                }
+            }
+            "SetCursorRect" => {
+                if let Ok((x, y, w, h)) = msg.read4::<i32, i32, i32, i32>() {
+                    self.contexts.entry(path).or_default().rect = Some((x, y, w, h));
+                }
+            }
+            "SetCursorRectV2" => {
+                // V2はデバイスピクセル + スケールなので論理ピクセルに変換
+                if let Ok((x, y, w, h, scale)) = msg.read5::<i32, i32, i32, i32, f64>() {
+                    let scale = if scale > 0.0 { scale } else { 1.0 };
+                    let to_logical = |v: i32| (f64::from(v) / scale).round() as i32;
+                    self.contexts.entry(path).or_default().rect =
+                        Some((to_logical(x), to_logical(y), to_logical(w), to_logical(h)));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// キャレット位置の追跡（DBusモニタスレッドを所有）
+///
+/// 監視スレッドは、すべてのクローンが破棄されると終了する
+#[derive(Clone, Default)]
+pub struct CaretTracker {
+    state: Arc<Mutex<TrackerState>>,
+    /// 監視スレッドはこれの弱参照だけを持ち、所有者がいなくなったことを知る
+    owner: Arc<()>,
+}
+
+impl CaretTracker {
+    /// モニタ用のDBus接続を確立して監視スレッドを起動
+    pub fn spawn() -> Result<Self> {
+        // モニタになった接続は他の用途に使えないため専用に接続する
+        let conn = DbusConnection::new_session()
+            .context("DBusセッションバスへの接続に失敗")?;
+
+        let rules: Vec<String> = [
+            "FocusIn",
+            "FocusOut",
+            "DestroyIC",
+            "SetCapability",
+            "SetCursorRect",
+            "SetCursorRectV2",
+        ]
+        .iter()
+        .map(|member| {
+            format!(
+                "type='method_call',interface='{}',member='{}'",
+                INPUT_CONTEXT_INTERFACE, member
+            )
+        })
+        .collect();
+
+        conn.with_proxy("org.freedesktop.DBus", "/org/freedesktop/DBus", Duration::from_millis(5000))
+            .method_call::<(), _, _, _>("org.freedesktop.DBus.Monitoring", "BecomeMonitor", (rules, 0u32))
+            .context("DBusモニタの開始に失敗")?;
+
+        let tracker = Self::default();
+        let state = Arc::clone(&tracker.state);
+        conn.start_receive(
+            MatchRule::new(),
+            Box::new(move |msg, _| {
+                if msg.msg_type() == MessageType::MethodCall {
+                    state.lock().unwrap().handle(&msg);
+                }
+                true
+            }),
+        );
+
+        let owner = Arc::downgrade(&tracker.owner);
+        std::thread::spawn(move || {
+            while owner.strong_count() > 0 {
+                if let Err(e) = conn.process(Duration::from_millis(1000)) {
+                    error!("キャレット監視エラー: {}", "Caret tracking error: {}", e);
+                    break;
+                }
+            }
+        });
+
+        Ok(tracker)
+    }
+
+    /// 現在フォーカスされている入力コンテキストのキャレット
+    pub fn current(&self) -> Option<CaretRect> {
+        self.state.lock().unwrap().current()
+    }
+}
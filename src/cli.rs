@@ -0,0 +1,54 @@
+// コマンドライン引数
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+
+const USAGE: &str = "\
+使い方: wayland_fcitx5_indicator [オプション]
+
+オプション:
+    --replace    起動中のインスタンスを終了させて置き換える
+    --waybar     入力メソッドが変わるたびにWaybar用のJSONを出力する（オーバーレイは表示しない）
+    --stats      表示までの遅延を記録し、終了時に段階ごとのp50/p95を表示する
+    --render-png <テキスト> <出力.png>
+                 オーバーレイと同じ描画をPNGファイルに書き出して終了する
+    -h, --help   このヘルプを表示";
+
+/// コマンドライン引数
+#[derive(Debug, Default)]
+pub struct Args {
+    /// 起動中のインスタンスを置き換える
+    pub replace: bool,
+    /// Waybarのcustomモジュール向けにJSONを出力する
+    pub waybar: bool,
+    /// 表示までの遅延の統計を終了時に表示する
+    pub stats: bool,
+    /// 表示テキストと出力先（オーバーレイの描画をPNGに書き出して終了する）
+    pub render_png: Option<(String, PathBuf)>,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self> {
+        let mut args = Self::default();
+        let mut iter = std::env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--replace" => args.replace = true,
+                "--waybar" => args.waybar = true,
+                "--stats" => args.stats = true,
+                "--render-png" => {
+                    let text = iter.next().context("--render-pngには表示テキストを指定してください")?;
+                    let path = iter.next().context("--render-pngには出力先を指定してください")?;
+                    args.render_png = Some((text, PathBuf::from(path)));
+                }
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                _ => anyhow::bail!("不明な引数です: {}（--helpで使い方を表示）", arg),
+            }
+        }
+        Ok(args)
+    }
+}
@@ -0,0 +1,446 @@
+// コンポジタ（Hyprland）のウィンドウ・モニタ情報
+// イベントソケット（.socket2.sock）を監視してキャッシュを最新に保つ
+
+use std::io::{self, BufRead, BufReader};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use hyprland::data::{Client, CursorPosition, FullscreenMode, Monitor, Monitors, Transforms};
+use hyprland::instance::Instance;
+use hyprland::prelude::*;
+
+use crate::placement::Rect;
+
+// キャッシュの更新が必要なイベント
+// ウィンドウのサイズ変更・再タイル配置はイベントが無いため、表示の開始時に問い合わせ直す
+const REFRESH_EVENTS: &[&str] = &[
+    "activewindowv2",
+    "openwindow",
+    "movewindow",
+    "movewindowv2",
+    "changefloatingmode",
+    "focusedmon",
+    "fullscreen",
+    "closewindow",
+    "windowtitle",
+    "windowtitlev2",
+    "workspace",
+    "workspacev2",
+    "monitoradded",
+    "monitoraddedv2",
+    "monitorremoved",
+    "monitorremovedv2",
+    "moveworkspace",
+    "moveworkspacev2",
+    "configreloaded",
+];
+
+/// イベントソケットへの再接続の最初の待ち時間（失敗するたびに倍にする）
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+/// 再接続の待ち時間の上限
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+/// 連続して失敗した場合に諦める（問い合わせ方式のままにする）までの回数
+const RECONNECT_MAX_ATTEMPTS: u32 = 10;
+/// この時間以上接続していれば、失敗の回数と待ち時間をリセットする
+const RECONNECT_STABLE_DURATION: Duration = Duration::from_secs(60);
+/// 監視スレッドが所有者（WindowTracker）の破棄を確認する間隔
+const OWNER_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// アクティブウィンドウの情報
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActiveWindow {
+    /// ウィンドウの識別子（フォーカス変化の判定用）
+    pub address: String,
+    /// アプリケーションのクラス
+    pub class: String,
+    pub title: String,
+    /// フルスクリーン表示中かどうか（最大化は含まない）
+    pub fullscreen: bool,
+    pub rect: Rect,
+}
+
+/// モニタの名前と矩形
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorRect {
+    pub name: String,
+    pub focused: bool,
+    pub rect: Rect,
+}
+
+/// キャッシュされたウィンドウ・モニタ情報
+#[derive(Default, PartialEq)]
+struct Snapshot {
+    active_window: Option<ActiveWindow>,
+    monitors: Vec<MonitorRect>,
+}
+
+impl Snapshot {
+    /// Hyprland IPCで現在の状態を取得（接続先が無い場合は空）
+    fn query(instance: Option<&Instance>) -> Self {
+        let Some(instance) = instance else {
+            return Self::default();
+        };
+        Self {
+            active_window: query_active_window(instance),
+            monitors: query_monitors(instance),
+        }
+    }
+}
+
+/// アクティブウィンドウ・モニタ情報の追跡
+///
+/// イベントソケットに接続できない場合は、取得のたびにIPCで問い合わせる。
+/// 監視スレッドは、すべてのクローンが破棄されると終了する
+#[derive(Clone, Default)]
+pub struct WindowTracker {
+    shared: Arc<Shared>,
+    /// 監視スレッドはこれの弱参照だけを持ち、所有者がいなくなったことを知る
+    owner: Arc<()>,
+}
+
+/// 監視スレッドと共有する状態
+#[derive(Default)]
+struct Shared {
+    /// 接続先のHyprland（Noneの場合は何も取得しない）
+    instance: Option<Instance>,
+    snapshot: Mutex<Snapshot>,
+    /// スナップショットが変化するたびに増える世代番号
+    generation: AtomicU64,
+    /// イベントソケットを監視中かどうか
+    listening: AtomicBool,
+    /// フォーカスが別のウィンドウに移ったときの通知先
+    focus_tx: Option<Sender<ActiveWindow>>,
+}
+
+impl WindowTracker {
+    /// 初期状態を取得してイベントソケットの監視スレッドを起動
+    ///
+    /// `hyprland_instance`はHyprlandのインスタンス（HYPRLAND_INSTANCE_SIGNATUREの値）で、
+    /// Noneの場合はウィンドウ・モニタ情報を取得しない。
+    /// `focus_tx`を渡すと、イベントソケットの監視中にフォーカスが別のウィンドウに移るたびに通知する
+    pub fn spawn(hyprland_instance: Option<&str>, focus_tx: Option<Sender<ActiveWindow>>) -> Self {
+        let Some(signature) = hyprland_instance else {
+            return Self::default();
+        };
+        let Some(dir) = instance_dir(signature) else {
+            warn!(
+                "Hyprlandのインスタンスが見つかりません（ウィンドウ・モニタ情報を使いません）: {}",
+                "Hyprland instance not found (not using window and monitor information): {}",
+                signature
+            );
+            return Self::default();
+        };
+
+        let tracker = Self {
+            shared: Arc::new(Shared {
+                instance: Instance::from_base_socket_path(dir.clone()).ok(),
+                focus_tx,
+                ..Shared::default()
+            }),
+            owner: Arc::new(()),
+        };
+
+        // 最初の接続は待つ（最初の表示からキャッシュを使えるように）
+        let path = dir.join(".socket2.sock");
+        let stream = tracker.shared.connect(&path);
+        let shared = Arc::clone(&tracker.shared);
+        let owner = Arc::downgrade(&tracker.owner);
+        std::thread::spawn(move || shared.listen(&path, stream, &owner));
+
+        tracker
+    }
+
+    /// アクティブウィンドウを別スレッドで問い合わせ直し、変わっていればキャッシュを更新（表示後の追従用）
+    ///
+    /// Hyprlandはサイズ変更や再タイル配置をイベントで通知しないため、監視中でもキャッシュの矩形は古い場合がある。
+    /// 表示の開始を待たせないよう、最初のフレームの後に呼び、変化は世代番号で追従させる
+    pub fn refresh_active_window(&self) {
+        if self.shared.instance.is_none() {
+            return;
+        }
+        let shared = Arc::clone(&self.shared);
+        std::thread::spawn(move || {
+            let Some(instance) = &shared.instance else {
+                return;
+            };
+            let active_window = query_active_window(instance);
+            let mut current = shared.snapshot.lock().unwrap();
+            if current.active_window != active_window {
+                current.active_window = active_window;
+                shared.generation.fetch_add(1, Ordering::AcqRel);
+            }
+        });
+    }
+
+    /// アクティブウィンドウ（キャッシュ）
+    pub fn active_window(&self) -> Option<ActiveWindow> {
+        self.shared.ensure_fresh();
+        self.shared.snapshot.lock().unwrap().active_window.clone()
+    }
+
+    /// 全モニタ
+    pub fn monitors(&self) -> Vec<MonitorRect> {
+        self.shared.ensure_fresh();
+        self.shared.snapshot.lock().unwrap().monitors.clone()
+    }
+
+    /// マウスポインタの位置（グローバル座標、キャッシュせず毎回問い合わせる）
+    pub fn pointer(&self) -> Option<(i32, i32)> {
+        let pointer = CursorPosition::instance_get(self.shared.instance.as_ref()?).ok()?;
+        Some((pointer.x as i32, pointer.y as i32))
+    }
+
+    /// 世代番号（表示中にウィンドウが動いたかの判定用、イベントと表示後の問い合わせで変化する）
+    pub fn generation(&self) -> u64 {
+        self.shared.generation.load(Ordering::Acquire)
+    }
+}
+
+impl Shared {
+    /// イベントソケットに接続し、現在の状態を取得して監視中にする
+    fn connect(&self, path: &Path) -> io::Result<UnixStream> {
+        let stream = UnixStream::connect(path)?;
+        self.refresh();
+        self.listening.store(true, Ordering::Release);
+        Ok(stream)
+    }
+
+    /// イベントを受け取ってキャッシュを更新（切断された場合は待ち時間を延ばしながら再接続）
+    ///
+    /// 所有者（WindowTracker）がすべて破棄されたら終了する
+    fn listen(&self, path: &Path, mut stream: io::Result<UnixStream>, owner: &Weak<()>) {
+        let mut delay = RECONNECT_INITIAL_DELAY;
+        let mut failures = 0;
+
+        loop {
+            match stream {
+                Ok(stream) => {
+                    let connected = Instant::now();
+                    self.read_events(stream, owner);
+                    // 再接続するまでは問い合わせ方式に戻る
+                    self.listening.store(false, Ordering::Release);
+                    if owner.strong_count() == 0 {
+                        return;
+                    }
+                    warn!("Hyprlandのイベントソケットが切断されました", "Hyprland event socket disconnected");
+
+                    if connected.elapsed() >= RECONNECT_STABLE_DURATION {
+                        delay = RECONNECT_INITIAL_DELAY;
+                        failures = 0;
+                    }
+                }
+                Err(e) => {
+                    warn!("Hyprlandのイベントソケットへの接続に失敗: {}", "Failed to connect to the Hyprland event socket: {}", e);
+                }
+            }
+
+            failures += 1;
+            if failures > RECONNECT_MAX_ATTEMPTS {
+                warn!(
+                    "イベントソケットへの再接続に{}回失敗しました（表示のたびに問い合わせます）",
+                    "Failed to reconnect to the event socket {} times (querying on every display)",
+                    RECONNECT_MAX_ATTEMPTS
+                );
+                return;
+            }
+            debug!(
+                "{}秒後にイベントソケットへ再接続します（{}/{}）",
+                "Reconnecting to the event socket in {} seconds ({}/{})",
+                delay.as_secs(),
+                failures,
+                RECONNECT_MAX_ATTEMPTS
+            );
+            if !sleep_while_owned(owner, delay) {
+                return;
+            }
+            delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+            stream = self.connect(path);
+        }
+    }
+
+    /// イベントを1行ずつ読んでキャッシュを更新（切断された場合と、所有者がいなくなった場合に戻る）
+    fn read_events(&self, stream: UnixStream, owner: &Weak<()>) {
+        // 所有者がいなくなったことに気付けるよう、読み込みにタイムアウトを設ける
+        if stream.set_read_timeout(Some(OWNER_CHECK_INTERVAL)).is_err() {
+            return;
+        }
+
+        // 接続時点のウィンドウはフォーカス変化として通知しない
+        let mut focused = self.focused_address();
+        let mut reader = BufReader::new(stream);
+        let mut line = Vec::new();
+        while owner.strong_count() > 0 {
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) => return,
+                Ok(_) => {
+                    let line_text = String::from_utf8_lossy(&line);
+                    let event = line_text.trim_end().split(">>").next().unwrap_or_default();
+                    if REFRESH_EVENTS.contains(&event) {
+                        self.refresh();
+                        self.notify_focus(&mut focused);
+                    }
+                    line.clear();
+                }
+                // 読みかけの行はlineに残っているので、続きから読む
+                Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {}
+                Err(_) => return,
+            }
+        }
+    }
+
+    /// IPCで状態を取得し直してキャッシュを更新
+    fn refresh(&self) {
+        let snapshot = Snapshot::query(self.instance.as_ref());
+        let mut current = self.snapshot.lock().unwrap();
+        if *current == snapshot {
+            return;
+        }
+        *current = snapshot;
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// フォーカスが別のウィンドウに移っていれば通知
+    ///
+    /// 実際のフォーカス変化だけを通知するため、イベントソケットの監視スレッドからのみ呼ぶ
+    /// （表示や問い合わせによるキャッシュの更新では通知しない）
+    fn notify_focus(&self, focused: &mut Option<String>) {
+        let window = self.snapshot.lock().unwrap().active_window.clone();
+        let address = window.as_ref().map(|window| window.address.clone());
+        if *focused == address {
+            return;
+        }
+        *focused = address;
+
+        if let (Some(focus_tx), Some(window)) = (&self.focus_tx, window) {
+            focus_tx.send(window).ok();
+        }
+    }
+
+    /// キャッシュされているアクティブウィンドウの識別子
+    fn focused_address(&self) -> Option<String> {
+        let snapshot = self.snapshot.lock().unwrap();
+        snapshot.active_window.as_ref().map(|window| window.address.clone())
+    }
+
+    /// 監視していない場合は問い合わせてキャッシュを更新
+    fn ensure_fresh(&self) {
+        if !self.listening.load(Ordering::Acquire) {
+            self.refresh();
+        }
+    }
+}
+
+/// 所有者がいる間だけ待つ（所有者がいなくなった場合はfalse）
+fn sleep_while_owned(owner: &Weak<()>, duration: Duration) -> bool {
+    let deadline = Instant::now() + duration;
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        if owner.strong_count() == 0 {
+            return false;
+        }
+        std::thread::sleep(remaining.min(OWNER_CHECK_INTERVAL));
+    }
+    owner.strong_count() > 0
+}
+
+/// インスタンスのソケットがあるディレクトリ（新しいHyprlandは$XDG_RUNTIME_DIR/hypr、古いものは/tmp/hypr）
+fn instance_dir(signature: &str) -> Option<PathBuf> {
+    let mut candidates = Vec::new();
+    if let Ok(runtime_dir) = std::env::var("XDG_RUNTIME_DIR") {
+        candidates.push(PathBuf::from(runtime_dir).join("hypr").join(signature));
+    }
+    candidates.push(PathBuf::from("/tmp/hypr").join(signature));
+
+    candidates
+        .into_iter()
+        .find(|dir| dir.join(".socket2.sock").exists())
+}
+
+/// アクティブウィンドウの位置とサイズを取得
+fn query_active_window(instance: &Instance) -> Option<ActiveWindow> {
+    // アクティブなウィンドウを取得
+    let active_window = Client::instance_get_active(instance).ok()??;
+
+    Some(ActiveWindow {
+        address: active_window.address.to_string(),
+        class: active_window.class,
+        title: active_window.title,
+        fullscreen: matches!(
+            active_window.fullscreen,
+            FullscreenMode::Fullscreen | FullscreenMode::MaximizedFullscreen
+        ),
+        rect: Rect {
+            x: active_window.at.0 as i32,
+            y: active_window.at.1 as i32,
+            width: active_window.size.0 as i32,
+            height: active_window.size.1 as i32,
+        },
+    })
+}
+
+/// 全モニタの名前と矩形
+fn query_monitors(instance: &Instance) -> Vec<MonitorRect> {
+    let Ok(monitors) = Monitors::instance_get(instance) else {
+        return Vec::new();
+    };
+
+    monitors
+        .into_iter()
+        .map(|monitor| MonitorRect {
+            rect: monitor_rect(&monitor),
+            name: monitor.name,
+            focused: monitor.focused,
+        })
+        .collect()
+}
+
+/// モニタの論理座標での矩形（スケールと回転を考慮）
+fn monitor_rect(monitor: &Monitor) -> Rect {
+    let scale = if monitor.scale > 0.0 { monitor.scale } else { 1.0 };
+    let width = (f32::from(monitor.width) / scale).round() as i32;
+    let height = (f32::from(monitor.height) / scale).round() as i32;
+
+    let (width, height) = match monitor.transform {
+        Transforms::Normal90 | Transforms::Normal270 | Transforms::Flipped90 | Transforms::Flipped270 => {
+            (height, width)
+        }
+        _ => (width, height),
+    };
+
+    Rect {
+        x: monitor.x,
+        y: monitor.y,
+        width,
+        height,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn event_thread_stops_when_tracker_is_dropped() {
+        // 古いHyprlandと同じ/tmp/hypr以下に偽のイベントソケットを置く
+        let signature = format!("wayland_fcitx5_indicator-test-{}", std::process::id());
+        let dir = PathBuf::from("/tmp/hypr").join(&signature);
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        let listener = UnixListener::bind(dir.join(".socket2.sock")).unwrap();
+
+        let tracker = WindowTracker::spawn(Some(&signature), None);
+        let (mut stream, _) = listener.accept().unwrap();
+        drop(tracker);
+
+        // 監視スレッドが終了すると接続が閉じられる
+        stream.set_read_timeout(Some(OWNER_CHECK_INTERVAL * 5)).unwrap();
+        let closed = matches!(stream.read(&mut [0; 1]), Ok(0));
+        std::fs::remove_dir_all(&dir).ok();
+        assert!(closed, "監視スレッドが終了していません");
+    }
+}
@@ -1,32 +1,667 @@
-use serde::Deserialize;
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock, RwLock};
 
-#[derive(Debug, Deserialize)]
+pub use crate::validation::{InvalidConfig, Problem, Severity};
+
+/// 組み込みの設定（設定ファイルが無い場合と、設定ファイルで省略したフィールドに使用）
+const BUILT_IN_CONFIG: &str = include_str!("../config.ron");
+
+/// 設定ファイルで省略したフィールドの値（組み込みの設定の同じフィールドの値）
+///
+/// `#[serde(default = "built_in::overlay_width")]` のように指定する。
+/// 組み込みの設定はすべてのフィールドを書いているので、それ自身の解析では呼ばれない
+mod built_in {
+    use super::*;
+
+    macro_rules! fields {
+        ($($name:ident: $ty:ty = $($field:ident).+;)*) => {
+            $(
+                pub(super) fn $name() -> $ty {
+                    Config::built_in().$($field).+.clone()
+                }
+            )*
+        };
+    }
+
+    fields! {
+        input_method_names: HashMap<String, String> = input_method_names;
+        overlay: OverlayConfig = overlay;
+        animation: AnimationConfig = animation;
+        placement: PlacementConfig = placement;
+        indicator: IndicatorConfig = indicator;
+        focus_trigger: FocusTriggerConfig = focus_trigger;
+        rules: Vec<Rule> = rules;
+        suppress: SuppressConfig = suppress;
+        tray: TrayConfig = tray;
+        hooks: HooksConfig = hooks;
+        logging: LoggingConfig = logging;
+
+        overlay_width: u32 = overlay.width;
+        overlay_height: u32 = overlay.height;
+        overlay_font_size: f64 = overlay.font_size;
+        overlay_font_family: String = overlay.font_family;
+        overlay_padding: f64 = overlay.padding;
+        overlay_corner_radius: f64 = overlay.corner_radius;
+        overlay_theme: Theme = overlay.theme;
+        theme_background: (f64, f64, f64, f64) = overlay.theme.background;
+        theme_box_color: (f64, f64, f64, f64) = overlay.theme.box_color;
+        theme_text: (f64, f64, f64, f64) = overlay.theme.text;
+
+        animation_display_duration_ms: u64 = animation.display_duration_ms;
+        animation_fade_duration_ms: u64 = animation.fade_duration_ms;
+        animation_fade_frames: u32 = animation.fade_frames;
+        animation_reduced_motion: ReducedMotion = animation.reduced_motion;
+
+        placement_mode: PlacementMode = placement.mode;
+        placement_anchor: PlacementAnchor = placement.anchor;
+        placement_margin: i32 = placement.margin;
+        placement_offset_x: i32 = placement.offset_x;
+        placement_offset_y: i32 = placement.offset_y;
+        placement_cursor_gap: i32 = placement.cursor_gap;
+        placement_outputs: OutputSelection = placement.outputs;
+
+        indicator_mode: IndicatorMode = indicator.mode;
+        indicator_badge: BadgeConfig = indicator.badge;
+        indicator_backend: DisplayBackend = indicator.backend;
+        badge_width: u32 = indicator.badge.width;
+        badge_height: u32 = indicator.badge.height;
+        badge_font_size: f64 = indicator.badge.font_size;
+        badge_padding: f64 = indicator.badge.padding;
+        badge_corner_radius: f64 = indicator.badge.corner_radius;
+        badge_anchor: PlacementAnchor = indicator.badge.anchor;
+        badge_margin: i32 = indicator.badge.margin;
+        badge_enlarge_scale: f64 = indicator.badge.enlarge_scale;
+        badge_enlarge_duration_ms: u64 = indicator.badge.enlarge_duration_ms;
+
+        focus_trigger_enabled: bool = focus_trigger.enabled;
+        focus_trigger_debounce_ms: u64 = focus_trigger.debounce_ms;
+        focus_trigger_exclude_classes: Vec<String> = focus_trigger.exclude_classes;
+
+        tray_enabled: bool = tray.enabled;
+
+        hooks_on_change: Vec<String> = hooks.on_change;
+        hooks_timeout_ms: u64 = hooks.timeout_ms;
+
+        logging_level: LogLevel = logging.level;
+        logging_output: LogOutput = logging.output;
+        logging_language: LogLanguage = logging.language;
+
+        suppress_fullscreen: SuppressAction = suppress.fullscreen;
+        suppress_locked: SuppressAction = suppress.locked;
+    }
+}
+
+/// 設定
+///
+/// 省略したフィールドは組み込みの設定（プロジェクトルートの`config.ron`）の値になる。
+/// 入れ子の設定も同様で、変えたいフィールドだけを書けばよい
+/// （`input_method_names`や`rules`などのマップ・リストは、書いた場合は丸ごと置き換える）
+#[derive(Debug, Clone, Deserialize)]
 pub struct Config {
+    /// 入力メソッドごとの表示テキスト（無い入力メソッドは名前をそのまま表示）
+    #[serde(default = "built_in::input_method_names")]
     pub input_method_names: HashMap<String, String>,
+    /// オーバーレイの外観
+    #[serde(default = "built_in::overlay")]
     pub overlay: OverlayConfig,
+    /// 表示時間とフェードアウト
+    #[serde(default = "built_in::animation")]
     pub animation: AnimationConfig,
+    /// 配置設定
+    #[serde(default = "built_in::placement")]
+    pub placement: PlacementConfig,
+    /// 表示方式
+    #[serde(default = "built_in::indicator")]
+    pub indicator: IndicatorConfig,
+    /// フォーカス変化時の表示
+    #[serde(default = "built_in::focus_trigger")]
+    pub focus_trigger: FocusTriggerConfig,
+    /// アプリケーションごとのルール（最初に一致したものを適用、`[]`でルールなし）
+    #[serde(default = "built_in::rules")]
+    pub rules: Vec<Rule>,
+    /// フルスクリーン・画面ロック中の表示
+    #[serde(default = "built_in::suppress")]
+    pub suppress: SuppressConfig,
+    /// トレイアイコン（StatusNotifierItem）
+    #[serde(default = "built_in::tray")]
+    pub tray: TrayConfig,
+    /// 入力メソッド変更時に実行するコマンド
+    #[serde(default = "built_in::hooks")]
+    pub hooks: HooksConfig,
+    /// ログ出力（レベル・出力先・言語）
+    #[serde(default = "built_in::logging")]
+    pub logging: LoggingConfig,
 }
 
-#[derive(Debug, Deserialize)]
+impl Default for Config {
+    fn default() -> Self {
+        Self::built_in().clone()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct OverlayConfig {
+    #[serde(default = "built_in::overlay_width")]
     pub width: u32,
+    #[serde(default = "built_in::overlay_height")]
     pub height: u32,
+    #[serde(default = "built_in::overlay_font_size")]
     pub font_size: f64,
+    #[serde(default = "built_in::overlay_font_family")]
     pub font_family: String,
+    /// 外枠から角丸ボックスまでの余白（ピクセル）
+    #[serde(default = "built_in::overlay_padding")]
+    pub padding: f64,
+    /// 角丸ボックスの角の半径（ピクセル）
+    #[serde(default = "built_in::overlay_corner_radius")]
+    pub corner_radius: f64,
+    /// 配色
+    #[serde(default = "built_in::overlay_theme")]
+    pub theme: Theme,
+}
+
+/// 配色（RGBA、各成分0.0〜1.0）
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Theme {
+    /// 外側の背景
+    #[serde(default = "built_in::theme_background")]
+    pub background: (f64, f64, f64, f64),
+    /// 角丸ボックス
+    #[serde(default = "built_in::theme_box_color")]
+    pub box_color: (f64, f64, f64, f64),
+    /// 文字
+    #[serde(default = "built_in::theme_text")]
+    pub text: (f64, f64, f64, f64),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct AnimationConfig {
+    /// 表示時間（ミリ秒）
+    #[serde(default = "built_in::animation_display_duration_ms")]
     pub display_duration_ms: u64,
+    /// フェードアウト時間（ミリ秒）
+    #[serde(default = "built_in::animation_fade_duration_ms")]
     pub fade_duration_ms: u64,
+    /// フェードアウトのフレーム数
+    #[serde(default = "built_in::animation_fade_frames")]
     pub fade_frames: u32,
+    /// アニメーション削減モード
+    #[serde(default = "built_in::animation_reduced_motion")]
+    pub reduced_motion: ReducedMotion,
+}
+
+/// アニメーション削減モード
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ReducedMotion {
+    /// 通常どおりフェードアウトする
+    Off,
+    /// フェードを行わず、表示して消すだけ
+    On,
+    /// デスクトップのアクセシビリティ設定に従う
+    Auto,
+}
+
+/// オーバーレイの配置設定
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlacementConfig {
+    /// 配置の基準
+    #[serde(default = "built_in::placement_mode")]
+    pub mode: PlacementMode,
+    /// 基準矩形（ウィンドウ・出力）内のどこに置くか
+    #[serde(default = "built_in::placement_anchor")]
+    pub anchor: PlacementAnchor,
+    /// 基準矩形の端からの余白（ピクセル、Center以外で有効）
+    #[serde(default = "built_in::placement_margin")]
+    pub margin: i32,
+    /// 最終位置に加えるオフセット（ピクセル）
+    ///
+    /// 出力の位置・大きさをコンポジタから取得できない場合は、レイヤーシェルのアンカーで配置するため
+    /// 中央揃えの軸（Centerの両方、Top・Bottomのx、Left・Rightのy）のオフセットは反映されない
+    #[serde(default = "built_in::placement_offset_x")]
+    pub offset_x: i32,
+    #[serde(default = "built_in::placement_offset_y")]
+    pub offset_y: i32,
+    /// キャレット・ポインタとオーバーレイの間隔（ピクセル）
+    #[serde(default = "built_in::placement_cursor_gap")]
+    pub cursor_gap: i32,
+    /// 表示する出力
+    #[serde(default = "built_in::placement_outputs")]
+    pub outputs: OutputSelection,
+}
+
+/// 配置の基準
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum PlacementMode {
+    /// アクティブウィンドウ内（anchorで位置を指定）
+    ActiveWindow,
+    /// フォーカス中の出力内（anchorで位置を指定）
+    Output,
+    /// グローバル座標での固定位置（オーバーレイの左上）
+    Absolute { x: i32, y: i32 },
+    /// テキストキャレットの直下（取得できない場合はActiveWindowと同じ）
+    Caret,
+    /// マウスポインタの直下（取得できない場合はActiveWindowと同じ）
+    Pointer,
+}
+
+/// オーバーレイを表示する出力
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub enum OutputSelection {
+    /// コンポジタが選ぶ出力（通常はフォーカス中の出力）
+    Focused,
+    /// すべての出力
+    All,
+    /// 名前（wl_outputのname、例: "DP-1"）で指定した出力
+    Named(Vec<String>),
+}
+
+/// 基準矩形内の配置位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum PlacementAnchor {
+    Center,
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// 表示方式の設定
+#[derive(Debug, Clone, Deserialize)]
+pub struct IndicatorConfig {
+    #[serde(default = "built_in::indicator_mode")]
+    pub mode: IndicatorMode,
+    /// 常時表示バッジの設定
+    #[serde(default = "built_in::indicator_badge")]
+    pub badge: BadgeConfig,
+    /// 表示に使う仕組み（省略時はレイヤーシェルが無ければデスクトップ通知）
+    #[serde(default = "built_in::indicator_backend")]
+    pub backend: DisplayBackend,
+}
+
+/// 表示に使う仕組み
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum DisplayBackend {
+    /// レイヤーシェルが使えればオーバーレイ、使えなければデスクトップ通知
+    Auto,
+    /// レイヤーシェルのオーバーレイ（使えない場合はエラー）
+    LayerShell,
+    /// デスクトップ通知（org.freedesktop.Notifications）
+    Notification,
+}
+
+/// 表示方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum IndicatorMode {
+    /// 切り替え時に表示してフェードアウト
+    Flash,
+    /// 出力の隅に小さなバッジを常時表示
+    Persistent,
+    /// 常時表示バッジとフラッシュ表示の両方
+    Both,
+}
+
+impl IndicatorMode {
+    pub fn flash(self) -> bool {
+        matches!(self, Self::Flash | Self::Both)
+    }
+
+    pub fn persistent(self) -> bool {
+        matches!(self, Self::Persistent | Self::Both)
+    }
+}
+
+/// 常時表示バッジの設定
+#[derive(Debug, Clone, Deserialize)]
+pub struct BadgeConfig {
+    #[serde(default = "built_in::badge_width")]
+    pub width: u32,
+    #[serde(default = "built_in::badge_height")]
+    pub height: u32,
+    #[serde(default = "built_in::badge_font_size")]
+    pub font_size: f64,
+    #[serde(default = "built_in::badge_padding")]
+    pub padding: f64,
+    #[serde(default = "built_in::badge_corner_radius")]
+    pub corner_radius: f64,
+    /// 出力内の位置
+    #[serde(default = "built_in::badge_anchor")]
+    pub anchor: PlacementAnchor,
+    /// 出力の端からの余白（ピクセル）
+    #[serde(default = "built_in::badge_margin")]
+    pub margin: i32,
+    /// 切り替え時に一時的に拡大する倍率（1.0で拡大しない）
+    #[serde(default = "built_in::badge_enlarge_scale")]
+    pub enlarge_scale: f64,
+    /// 拡大表示の時間（ミリ秒）
+    #[serde(default = "built_in::badge_enlarge_duration_ms")]
+    pub enlarge_duration_ms: u64,
+}
+
+/// フォーカス変化時の表示設定
+#[derive(Debug, Clone, Deserialize)]
+pub struct FocusTriggerConfig {
+    /// フォーカスが別のウィンドウに移ったときに現在の入力メソッドを表示する
+    #[serde(default = "built_in::focus_trigger_enabled")]
+    pub enabled: bool,
+    /// 連続したフォーカス変化をまとめる時間（ミリ秒）
+    #[serde(default = "built_in::focus_trigger_debounce_ms")]
+    pub debounce_ms: u64,
+    /// 表示しないアプリケーションのクラス
+    #[serde(default = "built_in::focus_trigger_exclude_classes")]
+    pub exclude_classes: Vec<String>,
+}
+
+/// トレイアイコンの設定
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrayConfig {
+    /// StatusNotifierItemとしてトレイに現在の入力メソッドを表示する
+    #[serde(default = "built_in::tray_enabled")]
+    pub enabled: bool,
+}
+
+/// フックの設定
+#[derive(Debug, Clone, Deserialize)]
+pub struct HooksConfig {
+    /// 入力メソッドが変わったときに実行するコマンド（sh -cで順に実行）
+    ///
+    /// 環境変数IM_OLD、IM_NEW、IM_LABEL、WINDOW_CLASSを渡す
+    #[serde(default = "built_in::hooks_on_change")]
+    pub on_change: Vec<String>,
+    /// コマンドごとのタイムアウト（ミリ秒、超えた場合は強制終了）
+    #[serde(default = "built_in::hooks_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+/// ログ出力の設定
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoggingConfig {
+    /// 出力するレベル（環境変数RUST_LOGが設定されている場合はそちらを優先）
+    #[serde(default = "built_in::logging_level")]
+    pub level: LogLevel,
+    /// 出力先
+    #[serde(default = "built_in::logging_output")]
+    pub output: LogOutput,
+    /// メッセージの言語
+    #[serde(default = "built_in::logging_language")]
+    pub language: LogLanguage,
+}
+
+/// ログのレベル（指定したレベル以上を出力）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// ログの出力先
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum LogOutput {
+    /// systemdから起動された場合（JOURNAL_STREAMがある場合）はJournald、それ以外はTerminal
+    Auto,
+    /// 時刻とレベルを付けて標準エラー出力へ
+    Terminal,
+    /// journaldが優先度として解釈する接頭辞（<3>など）を付けて標準エラー出力へ
+    Journald,
+}
+
+/// ログメッセージの言語
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum LogLanguage {
+    /// ロケール（LC_ALL / LC_MESSAGES / LANG）が日本語ならJapanese、それ以外はEnglish
+    Auto,
+    Japanese,
+    English,
+}
+
+/// フルスクリーン・画面ロック中の表示設定
+#[derive(Debug, Clone, Deserialize)]
+pub struct SuppressConfig {
+    /// アクティブウィンドウがフルスクリーンのとき
+    #[serde(default = "built_in::suppress_fullscreen")]
+    pub fullscreen: SuppressAction,
+    /// セッションがロックされているとき（logindのLockedHint）
+    #[serde(default = "built_in::suppress_locked")]
+    pub locked: SuppressAction,
+}
+
+/// 抑制時の動作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum SuppressAction {
+    /// 通常どおりOverlayレイヤーに表示
+    Show,
+    /// Topレイヤーに表示（フルスクリーンのウィンドウやロック画面の下になる）
+    Demote,
+    /// 表示しない
+    Hide,
+}
+
+/// アプリケーションごとのルール
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+    /// ウィンドウクラスの正規表現（省略時は任意のクラスに一致）
+    #[serde(default, deserialize_with = "deserialize_regex")]
+    pub class: Option<Regex>,
+    /// ウィンドウタイトルの正規表現（省略時は任意のタイトルに一致）
+    #[serde(default, deserialize_with = "deserialize_regex")]
+    pub title: Option<Regex>,
+    /// オーバーレイを表示しない
+    #[serde(default)]
+    pub suppress: bool,
+    /// 配置の上書き
+    #[serde(default)]
+    pub placement: Option<RulePlacement>,
+    /// サイズ・見た目の上書き
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+    #[serde(default)]
+    pub font_size: Option<f64>,
+    #[serde(default)]
+    pub theme: Option<Theme>,
+    /// 表示時間の上書き（ミリ秒）
+    #[serde(default)]
+    pub display_duration_ms: Option<u64>,
+}
+
+/// ルールによる配置の上書き（省略した項目は `placement` の値のまま）
+#[derive(Debug, Clone, Deserialize)]
+pub struct RulePlacement {
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub mode: Option<PlacementMode>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub anchor: Option<PlacementAnchor>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub margin: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub offset_x: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub offset_y: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub cursor_gap: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub outputs: Option<OutputSelection>,
+}
+
+impl RulePlacement {
+    /// 指定した項目だけを配置設定に上書きする
+    pub fn apply(&self, placement: &mut PlacementConfig) {
+        if let Some(mode) = self.mode {
+            placement.mode = mode;
+        }
+        if let Some(anchor) = self.anchor {
+            placement.anchor = anchor;
+        }
+        if let Some(margin) = self.margin {
+            placement.margin = margin;
+        }
+        if let Some(offset_x) = self.offset_x {
+            placement.offset_x = offset_x;
+        }
+        if let Some(offset_y) = self.offset_y {
+            placement.offset_y = offset_y;
+        }
+        if let Some(cursor_gap) = self.cursor_gap {
+            placement.cursor_gap = cursor_gap;
+        }
+        if let Some(outputs) = &self.outputs {
+            placement.outputs = outputs.clone();
+        }
+    }
+}
+
+impl Rule {
+    /// ウィンドウのクラスとタイトルに一致するかどうか
+    pub fn matches(&self, class: &str, title: &str) -> bool {
+        self.class.as_ref().is_none_or(|regex| regex.is_match(class))
+            && self.title.as_ref().is_none_or(|regex| regex.is_match(title))
+    }
+
+    /// オーバーレイの見た目（バッファの内容）を変えるかどうか
+    pub fn overrides_appearance(&self) -> bool {
+        self.width.is_some() || self.height.is_some() || self.font_size.is_some() || self.theme.is_some()
+    }
+}
+
+/// 書かれている項目をSomeにする（`PlacementConfig` と同じ書き方で、`Some(...)` で囲まずに書ける）
+fn deserialize_some<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// 正規表現の文字列をコンパイル
+fn deserialize_regex<'de, D>(deserializer: D) -> Result<Option<Regex>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let pattern = String::deserialize(deserializer)?;
+    Regex::new(&pattern)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
 }
 
 impl Config {
+    /// 組み込みの設定（プロジェクトルートの`config.ron`）
+    pub fn built_in() -> &'static Config {
+        static BUILT_IN: OnceLock<Config> = OnceLock::new();
+        BUILT_IN.get_or_init(|| ron::from_str(BUILT_IN_CONFIG).expect("Failed to parse config.ron"))
+    }
+
+    /// 設定をロード（設定ファイルが読めない場合は組み込みの設定を使用）
     pub fn load() -> Self {
-        const CONFIG_STR: &str = include_str!("../config.ron");
-        ron::from_str(CONFIG_STR).expect("Failed to parse config.ron")
+        Self::try_load().unwrap_or_else(|e| {
+            error!(
+                "設定ファイルの読み込みに失敗（組み込みの設定を使用）: {:#}",
+                "Failed to load the config file (using the built-in config): {:#}",
+                e
+            );
+            Self::default()
+        })
+    }
+
+    /// 設定ファイルがあれば読み込んで検証し、無ければ組み込みの設定を検証して返す
+    pub fn try_load() -> Result<Self> {
+        match Self::path() {
+            Some((path, format)) => {
+                let content = std::fs::read_to_string(&path)
+                    .with_context(|| format!("{}を読み込めません", path.display()))?;
+                let config = Self::parse(&content, format)
+                    .with_context(|| format!("{}の解析に失敗", path.display()))?;
+                config.check().with_context(|| format!("{}の設定値が不正です", path.display()))?;
+                Ok(config)
+            }
+            None => {
+                // 組み込みの設定でも、指定したフォントが無いといった警告は出す
+                let config = Self::default();
+                config.check().context("組み込みの設定値が不正です")?;
+                Ok(config)
+            }
+        }
+    }
+
+    /// 設定を解析（省略したフィールドは組み込みの設定の値になる）
+    pub fn parse(content: &str, format: ConfigFormat) -> Result<Self> {
+        let config = match format {
+            ConfigFormat::Ron => ron::from_str(content)?,
+            ConfigFormat::Toml => toml::from_str(content)?,
+        };
+        Ok(config)
+    }
+
+    /// 設定ファイルのディレクトリ（$XDG_CONFIG_HOME/wayland_fcitx5_indicator）
+    pub fn dir() -> Option<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("wayland_fcitx5_indicator"))
+    }
+
+    /// 設定ファイルのパスと形式（config.ron、無ければconfig.toml、どちらも無ければNone）
+    pub fn path() -> Option<(PathBuf, ConfigFormat)> {
+        let dir = Self::dir()?;
+        let mut found = [ConfigFormat::Ron, ConfigFormat::Toml]
+            .into_iter()
+            .map(|format| (dir.join(format.file_name()), format))
+            .filter(|(path, _)| path.exists());
+
+        let first = found.next()?;
+        if let Some((ignored, _)) = found.next() {
+            warn!(
+                "{}があるため{}は読み込みません",
+                "Ignoring {1} because {0} exists",
+                first.0.display(),
+                ignored.display()
+            );
+        }
+        Some(first)
+    }
+
+    /// ウィンドウに最初に一致するルール（インデックスとルール）
+    pub fn matching_rule(&self, class: &str, title: &str) -> Option<(usize, &Rule)> {
+        self.rules
+            .iter()
+            .enumerate()
+            .find(|(_, rule)| rule.matches(class, title))
+    }
+
+    /// ルールの上書きを適用した設定
+    pub fn with_rule(&self, rule: &Rule) -> Config {
+        let mut config = self.clone();
+        if let Some(placement) = &rule.placement {
+            placement.apply(&mut config.placement);
+        }
+        if let Some(width) = rule.width {
+            config.overlay.width = width;
+        }
+        if let Some(height) = rule.height {
+            config.overlay.height = height;
+        }
+        if let Some(font_size) = rule.font_size {
+            config.overlay.font_size = font_size;
+        }
+        if let Some(theme) = rule.theme {
+            config.overlay.theme = theme;
+        }
+        if let Some(display_duration_ms) = rule.display_duration_ms {
+            config.animation.display_duration_ms = display_duration_ms;
+        }
+        config
     }
 
     pub fn get_display_text(&self, input_method: &str) -> String {
@@ -36,3 +671,146 @@ impl Config {
             .unwrap_or_else(|| input_method.to_string())
     }
 }
+
+/// 設定ファイルの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    /// RON（config.ron）
+    Ron,
+    /// TOML（config.toml）
+    Toml,
+}
+
+impl ConfigFormat {
+    /// 設定ディレクトリでのファイル名
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::Ron => "config.ron",
+            Self::Toml => "config.toml",
+        }
+    }
+}
+
+/// 実行中に再読み込みできる設定
+///
+/// 読み込んだ設定はArcで共有し、再読み込み時は丸ごと置き換える
+#[derive(Clone)]
+pub struct SharedConfig(Arc<RwLock<Arc<Config>>>);
+
+impl SharedConfig {
+    pub fn new(config: Config) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(config))))
+    }
+
+    /// 現在の設定
+    pub fn get(&self) -> Arc<Config> {
+        Arc::clone(&self.0.read().unwrap())
+    }
+
+    /// 設定を置き換える
+    pub fn replace(&self, config: Config) {
+        *self.0.write().unwrap() = Arc::new(config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_list_replaces_built_in_rules() {
+        let config = Config::parse("Config(rules: [])", ConfigFormat::Ron).unwrap();
+        let built_in = Config::built_in();
+
+        assert!(config.rules.is_empty());
+        assert_eq!(config.input_method_names, built_in.input_method_names);
+        assert_eq!(config.focus_trigger.exclude_classes, built_in.focus_trigger.exclude_classes);
+    }
+
+    #[test]
+    fn partial_ron_is_layered_over_built_in() {
+        let config = Config::parse(
+            "Config(overlay: OverlayConfig(font_size: 30.0), placement: PlacementConfig(mode: Output))",
+            ConfigFormat::Ron,
+        )
+        .unwrap();
+        let built_in = Config::built_in();
+
+        assert_eq!(config.overlay.font_size, 30.0);
+        assert_eq!(config.overlay.width, built_in.overlay.width);
+        assert_eq!(config.overlay.font_family, built_in.overlay.font_family);
+        assert_eq!(config.placement.mode, PlacementMode::Output);
+        assert_eq!(config.placement.margin, built_in.placement.margin);
+        assert_eq!(config.animation.fade_frames, built_in.animation.fade_frames);
+        assert_eq!(config.input_method_names, built_in.input_method_names);
+        assert_eq!(config.rules.len(), built_in.rules.len());
+    }
+
+    #[test]
+    fn rule_placement_is_layered_over_placement() {
+        let config = Config::parse(
+            r#"Config(
+                placement: PlacementConfig(mode: Output, margin: 40, outputs: Named(["DP-1"])),
+                rules: [Rule(class: "^kitty$", placement: Some((anchor: TopLeft)))],
+            )"#,
+            ConfigFormat::Ron,
+        )
+        .unwrap();
+
+        let (_, rule) = config.matching_rule("kitty", "").unwrap();
+        let placement = config.with_rule(rule).placement;
+        assert_eq!(placement.anchor, PlacementAnchor::TopLeft);
+        assert_eq!(placement.mode, PlacementMode::Output);
+        assert_eq!(placement.margin, 40);
+        assert_eq!(placement.outputs, OutputSelection::Named(vec!["DP-1".to_string()]));
+    }
+
+    #[test]
+    fn partial_toml_is_layered_over_built_in() {
+        let config = Config::parse(
+            r#"
+            [input_method_names]
+            mozc = "あ"
+
+            [overlay]
+            font_size = 30.0
+            theme = { text = [1.0, 0.0, 0.0, 1.0] }
+
+            [placement]
+            mode = { Absolute = { x = 100, y = 50 } }
+            outputs = { Named = ["DP-1"] }
+
+            [indicator]
+            backend = "Notification"
+
+            [[rules]]
+            class = "^mpv$"
+            suppress = true
+
+            [[rules]]
+            class = "^foot$"
+            width = 120
+            "#,
+            ConfigFormat::Toml,
+        )
+        .unwrap();
+        let built_in = Config::built_in();
+
+        // マップは丸ごと置き換える
+        assert_eq!(config.input_method_names, HashMap::from([("mozc".to_string(), "あ".to_string())]));
+        assert_eq!(config.overlay.font_size, 30.0);
+        assert_eq!(config.overlay.height, built_in.overlay.height);
+        assert_eq!(config.overlay.theme.text, (1.0, 0.0, 0.0, 1.0));
+        assert_eq!(config.overlay.theme.background, built_in.overlay.theme.background);
+        assert_eq!(config.placement.mode, PlacementMode::Absolute { x: 100, y: 50 });
+        assert_eq!(config.placement.outputs, OutputSelection::Named(vec!["DP-1".to_string()]));
+        assert_eq!(config.indicator.backend, DisplayBackend::Notification);
+        assert_eq!(config.indicator.mode, built_in.indicator.mode);
+        assert_eq!(config.animation.display_duration_ms, built_in.animation.display_duration_ms);
+
+        assert_eq!(config.rules.len(), 2);
+        assert!(config.rules[0].suppress && config.rules[0].matches("mpv", ""));
+        assert_eq!(config.rules[1].width, Some(120));
+        assert_eq!(config.rules[1].height, None);
+    }
+}
@@ -0,0 +1,263 @@
+// デーモン本体
+// 入力メソッドの変更を監視して表示スレッドに送り、DBusサービス・トレイ・フック・シグナルを扱う
+
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use crossbeam_channel::unbounded;
+use dbus::blocking::Connection as DbusConnection;
+use dbus_crossroads::Crossroads;
+
+use crate::accessibility;
+use crate::caret::CaretTracker;
+use crate::compositor::WindowTracker;
+use crate::config::{Config, PlacementMode, SharedConfig};
+use crate::display::{supervise_display, DisplayRequest};
+use crate::focus;
+use crate::hooks::{self, HookEvent};
+use crate::input_method::{self, get_current_input_method};
+use crate::latency::{self, Stage, Trace};
+use crate::lifecycle;
+use crate::logging;
+use crate::placement::PlacementSources;
+use crate::service::{self, DaemonState};
+use crate::tray;
+
+/// 終了時に表示スレッドの片付けを待つ時間の上限
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// デーモンの起動オプション
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// 起動中のインスタンスを置き換える
+    pub replace: bool,
+    /// 表示までの遅延を記録し、終了時に段階ごとのp50/p95を標準出力に表示する
+    pub stats: bool,
+}
+
+/// デーモンとして動作（SIGTERM / SIGINTを受け取るまで戻らない）
+///
+/// 既に起動している場合は、起動中のインスタンスに設定を再読み込みさせてすぐに戻る。
+/// ログの出力先は[`logging::init`]で事前に登録しておく
+pub fn run(options: &Options) -> Result<()> {
+    // 終了時（エラーで戻る場合も含む）に統計を表示
+    let _stats_report = options.stats.then(latency::enable_stats);
+
+    info!(
+        "fcitx5の入力メソッド変更の監視を開始します（v{}）",
+        "Starting to watch fcitx5 input method changes (v{})",
+        env!("CARGO_PKG_VERSION")
+    );
+
+    // 設定をロード
+    let config = SharedConfig::new(Config::load());
+    logging::configure(&config.get().logging);
+    info!("設定ファイルをロードしました", "Loaded the config file");
+
+    // 表示リクエスト用チャネル
+    let (tx, rx) = unbounded::<DisplayRequest>();
+
+    // DBus接続を確立
+    let dbus_conn = DbusConnection::new_session()
+        .context("DBusセッションバスへの接続に失敗")?;
+
+    // SIGTERM / SIGINTで終了、SIGHUPで設定を再読み込み
+    // （置き換えられた場合もシグナルと同じく終了させるため、名前の取得より先に登録する）
+    let signals = lifecycle::Signals::register()?;
+
+    // 多重起動の防止（既に起動している場合は設定を再読み込みさせて終了）
+    if !service::claim_name(&dbus_conn, options.replace, signals.terminate_flag())? {
+        if options.replace {
+            anyhow::bail!("起動中のインスタンスを置き換えられませんでした");
+        }
+        service::request_reload(&dbus_conn)?;
+        info!(
+            "既に起動しています。起動中のインスタンスの設定を再読み込みしました（置き換える場合は --replace を指定してください）",
+            "Already running; reloaded the running instance's config (use --replace to replace it)"
+        );
+        return Ok(());
+    }
+
+    // 起動時の設定（以下の監視の有無は再読み込みでは変わらない）
+    let initial_config = config.get();
+
+    // アニメーション削減設定（Autoの場合はデスクトップ設定を監視）
+    let reduced_motion = accessibility::reduced_motion_flag(
+        initial_config.animation.reduced_motion,
+        &dbus_conn,
+    )?;
+
+    // キャレット配置の場合はfcitx5の入力コンテキストを監視
+    let caret = if initial_config.placement.mode == PlacementMode::Caret {
+        match CaretTracker::spawn() {
+            Ok(tracker) => Some(tracker),
+            Err(e) => {
+                warn!(
+                    "キャレット監視を開始できません（ウィンドウ中央に表示します）: {:#}",
+                    "Cannot start caret tracking (showing at the window center): {:#}",
+                    e
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // 入力メソッド監視とDBusサービスで共有する状態
+    // （現在の入力メソッドを保存して重複表示を防ぐ）
+    let state = DaemonState {
+        config: config.clone(),
+        last_input_method: Arc::new(Mutex::new(String::new())),
+        paused: Arc::new(AtomicBool::new(false)),
+        tx: tx.clone(),
+    };
+
+    // フォーカス変化時の表示（有効な場合のみ通知を受け取る）
+    let focus_tx = if initial_config.focus_trigger.enabled {
+        let (focus_tx, focus_rx) = unbounded();
+        focus::spawn_focus_trigger(focus_rx, state.clone());
+        Some(focus_tx)
+    } else {
+        None
+    };
+
+    // アクティブウィンドウの変化をイベントソケットで監視（表示時の問い合わせを省く）
+    // （Hyprland以外のコンポジタではウィンドウ・モニタ情報を使わない）
+    let hyprland_instance = std::env::var("HYPRLAND_INSTANCE_SIGNATURE").ok();
+    if hyprland_instance.is_none() {
+        warn!(
+            "HYPRLAND_INSTANCE_SIGNATUREが無いため、ウィンドウ・モニタ情報を使いません",
+            "HYPRLAND_INSTANCE_SIGNATURE is not set, not using window and monitor information"
+        );
+    }
+    let windows = WindowTracker::spawn(hyprland_instance.as_deref(), focus_tx);
+    let sources = PlacementSources {
+        caret,
+        windows: windows.clone(),
+    };
+
+    // 専用表示スレッドを起動（Wayland接続を維持し、切断時は再接続する）
+    let config_clone = config.clone();
+    let paused = Arc::clone(&state.paused);
+    let (ready_tx, ready_rx) = unbounded();
+    let display = std::thread::spawn(move || {
+        supervise_display(rx, config_clone, reduced_motion, paused, sources, ready_tx);
+    });
+
+    // 初回の入力メソッドを取得して表示
+    if let Ok(current) = get_current_input_method() {
+        info!("初期入力メソッド: {}", "Initial input method: {}", current);
+        *state.last_input_method.lock().unwrap() = current.clone();
+        state.show_input_method(&current, Trace::start());
+    }
+
+    // 操作用のDBusサービスとトレイアイコン（有効な場合のみ）を公開
+    let mut crossroads = Crossroads::new();
+    service::register(&mut crossroads, state.clone());
+    let tray = if initial_config.tray.enabled {
+        Some(tray::register(&mut crossroads, &dbus_conn, state.clone())?)
+    } else {
+        None
+    };
+    service::serve(&dbus_conn, crossroads);
+    info!("DBusサービスを公開しました: {}", "Exported DBus service: {}", service::BUS_NAME);
+
+    // fcitx5のプロパティ変更シグナルを受けたら入力メソッドが変更されたかチェック
+    let state_clone = state.clone();
+    input_method::watch_traced(&dbus_conn, move |current, trace| {
+        state_clone.update_input_method(current, trace);
+    })?;
+
+    // fcitx-remoteコマンドの実行を監視する代替手段
+    // （より確実に変更を検知）
+    info!("DBusシグナル監視を開始しました", "Started watching DBus signals");
+
+    // 入力メソッド変更時のフック（専用スレッドで実行）
+    let hook_tx = hooks::spawn_hook_runner(config.clone());
+
+    let mut watchdog = lifecycle::Watchdog::new();
+    let mut ready = false;
+
+    // メインループ（ポーリング + DBusイベント処理）
+    let mut last_notified = String::new();
+    loop {
+        // 表示スレッドが再接続を諦めた場合は異常終了（systemdなどに再起動を任せる）
+        if display.is_finished() {
+            anyhow::bail!("表示スレッドが終了しました");
+        }
+
+        // WaylandとDBusの接続が済んだらsystemdに起動完了を通知
+        if !ready && ready_rx.try_recv().is_ok() {
+            lifecycle::notify_ready();
+            ready = true;
+        }
+        watchdog.ping();
+
+        if signals.terminate_requested() {
+            info!("終了します", "Exiting");
+            lifecycle::notify_stopping();
+            // 表示中のものを終えてから終了（待ち時間には上限を設ける）
+            tx.send(DisplayRequest::Shutdown).ok();
+            let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+            while !display.is_finished() && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            return Ok(());
+        }
+
+        if signals.take_reload() {
+            // 起動完了の前はREADY=1を送らない（起動完了時に送る）
+            if ready {
+                lifecycle::notify_reloading();
+            }
+            if let Err(e) = state.reload() {
+                error!(
+                    "設定ファイルの再読み込みに失敗（以前の設定のまま）: {:#}",
+                    "Failed to reload the config file (keeping the previous config): {:#}",
+                    e
+                );
+            }
+            if ready {
+                lifecycle::notify_ready();
+            }
+        }
+
+        // DBusイベント処理（タイムアウト付き）
+        dbus_conn.process(Duration::from_millis(500))?;
+
+        // 定期的にポーリングもする（シグナルが来ない場合のフォールバック）
+        let mut trace = Trace::start();
+        if let Ok(current) = get_current_input_method() {
+            trace.mark(Stage::Queried);
+            state.update_input_method(current, trace);
+        }
+
+        // 入力メソッドが変わっていればChangedシグナルの送信とフックの実行
+        // （フォーカス変化による変更もここでまとめて通知する）
+        let current = state.last_input_method.lock().unwrap().clone();
+        if current != last_notified {
+            let current_config = config.get();
+            let label = current_config.get_display_text(&current);
+            service::emit_changed(&dbus_conn, &current, &label);
+            if let Some(tray) = &tray {
+                tray.notify_changed(&dbus_conn);
+            }
+
+            if !current_config.hooks.on_change.is_empty() {
+                let window_class = windows.active_window().map(|window| window.class).unwrap_or_default();
+                hook_tx
+                    .send(HookEvent {
+                        old: last_notified,
+                        new: current.clone(),
+                        label,
+                        window_class,
+                    })
+                    .ok();
+            }
+            last_notified = current;
+        }
+    }
+}
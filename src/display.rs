@@ -0,0 +1,302 @@
+// 表示スレッド
+// Wayland接続を維持して表示リクエストを処理し、切断時はバックオフして再接続する
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use crossbeam_channel::RecvTimeoutError;
+
+use crate::badge::Badge;
+use crate::config::{Config, DisplayBackend, IndicatorMode, SharedConfig, SuppressAction};
+use crate::latency::{Stage, Trace};
+use crate::notification;
+use crate::overlay::{show_overlay_cached, OverlayCaches};
+use crate::placement::PlacementSources;
+use crate::session::SessionLock;
+use crate::wayland::{LayerShellUnavailable, WaylandContext};
+
+/// 再接続の待ち時間の初期値（失敗が続くたびに倍にする）
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+/// 再接続の待ち時間の上限
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+/// 連続して失敗した場合に諦めるまでの回数
+const RECONNECT_MAX_ATTEMPTS: u32 = 10;
+/// この時間以上動作していれば、失敗の回数と待ち時間をリセットする
+const RECONNECT_STABLE_DURATION: Duration = Duration::from_secs(60);
+/// 表示リクエストが無い間に接続を確認する間隔
+const CONNECTION_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 表示スレッドへのリクエスト
+pub enum DisplayRequest {
+    /// テキストを表示（受け取ってからの遅延を計測）
+    Show(String, Trace),
+    /// 一時停止・再開された（一時停止中はバッジを隠す）
+    PauseChanged,
+    /// 表示中のものを終えてから終了
+    Shutdown,
+}
+
+/// 表示スレッドを監視し、エラーで終了した場合はバックオフして再接続する
+///
+/// 終了のリクエストを受け取った場合と、再接続を諦めた場合に戻る
+pub fn supervise_display(
+    rx: crossbeam_channel::Receiver<DisplayRequest>,
+    config: SharedConfig,
+    reduced_motion: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    sources: PlacementSources,
+    ready_tx: crossbeam_channel::Sender<()>,
+) {
+    let mut delay = RECONNECT_INITIAL_DELAY;
+    let mut failures = 0;
+
+    loop {
+        let started = Instant::now();
+        let Err(e) = display_thread(&rx, &config, &reduced_motion, &paused, &sources, &ready_tx) else {
+            return;
+        };
+        error!("表示スレッドエラー: {:#}", "Display thread error: {:#}", e);
+
+        if started.elapsed() >= RECONNECT_STABLE_DURATION {
+            delay = RECONNECT_INITIAL_DELAY;
+            failures = 0;
+        }
+        failures += 1;
+        if failures > RECONNECT_MAX_ATTEMPTS {
+            error!(
+                "再接続に{}回失敗したため表示を停止します",
+                "Failed to reconnect {} times, stopping the display",
+                RECONNECT_MAX_ATTEMPTS
+            );
+            return;
+        }
+
+        warn!(
+            "{}秒後に再接続します（{}/{}）",
+            "Reconnecting in {} seconds ({}/{})",
+            delay.as_secs(),
+            failures,
+            RECONNECT_MAX_ATTEMPTS
+        );
+        if wait_discarding_requests(&rx, delay) {
+            return;
+        }
+        delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+    }
+}
+
+/// 再接続までの待機（切断中の表示リクエストは古いので捨てる、終了のリクエストならtrue）
+fn wait_discarding_requests(rx: &crossbeam_channel::Receiver<DisplayRequest>, delay: Duration) -> bool {
+    let deadline = Instant::now() + delay;
+    loop {
+        match rx.recv_deadline(deadline) {
+            Ok(DisplayRequest::Show(..) | DisplayRequest::PauseChanged) => {}
+            Ok(DisplayRequest::Shutdown) | Err(RecvTimeoutError::Disconnected) => return true,
+            Err(RecvTimeoutError::Timeout) => return false,
+        }
+    }
+}
+
+/// 専用表示スレッド（Wayland接続を1回だけ確立、バッファキャッシュを再利用）
+///
+/// 接続が切れた場合やコンポジタとのやり取りに失敗した場合はエラーを返す
+///
+/// 初期化が済んだらready_txに通知する
+fn display_thread(
+    rx: &crossbeam_channel::Receiver<DisplayRequest>,
+    config: &SharedConfig,
+    reduced_motion: &AtomicBool,
+    paused: &AtomicBool,
+    sources: &PlacementSources,
+    ready_tx: &crossbeam_channel::Sender<()>,
+) -> Result<()> {
+    // 表示の仕組みを選択（Autoはレイヤーシェルが無ければデスクトップ通知）
+    let backend = config.get().indicator.backend;
+    if backend == DisplayBackend::Notification {
+        return notification::run(rx, config, ready_tx);
+    }
+
+    let mut wayland = match WaylandContext::connect() {
+        Ok(wayland) => wayland,
+        Err(e) if backend == DisplayBackend::Auto && e.is::<LayerShellUnavailable>() => {
+            warn!("{}（デスクトップ通知で表示します）", "{} (falling back to desktop notifications)", e);
+            return notification::run(rx, config, ready_tx);
+        }
+        Err(e) => return Err(e),
+    };
+
+    info!("Wayland接続確立完了", "Connected to Wayland");
+
+    let mut current_config = config.get();
+    let mut resources = DisplayResources::new(&mut wayland, &current_config, sources, paused)?;
+
+    info!("初期化完了、表示リクエストを待機中", "Initialized, waiting for display requests");
+    ready_tx.send(()).ok();
+
+    // 表示リクエストを処理（待機中も定期的に接続を確認し、画面ロックの変化でバッジを隠す）
+    let no_lock_changes = crossbeam_channel::never();
+    loop {
+        let lock_changes = resources
+            .session_lock
+            .as_ref()
+            .map_or(&no_lock_changes, SessionLock::changes);
+        let request = crossbeam_channel::select! {
+            recv(rx) -> request => request,
+            recv(lock_changes) -> _ => {
+                resources.update_badge_visibility(&mut wayland, &current_config, paused)?;
+                continue;
+            }
+            default(CONNECTION_CHECK_INTERVAL) => {
+                wayland.check_connection()?;
+                continue;
+            }
+        };
+        let (text, mut trace) = match request {
+            Ok(DisplayRequest::Show(text, trace)) => (text, trace),
+            Ok(DisplayRequest::PauseChanged) => {
+                resources.update_badge_visibility(&mut wayland, &current_config, paused)?;
+                continue;
+            }
+            Ok(DisplayRequest::Shutdown) | Err(_) => break,
+        };
+        let reduced_motion = reduced_motion.load(Ordering::Relaxed);
+
+        // 設定が再読み込みされていればキャッシュとバッジを作り直す
+        let latest_config = config.get();
+        if !Arc::ptr_eq(&latest_config, &current_config) {
+            resources.destroy();
+            current_config = latest_config;
+            resources = DisplayResources::new(&mut wayland, &current_config, sources, paused)?;
+        }
+        let config = &*current_config;
+        let DisplayResources {
+            overlay_caches,
+            badge,
+            session_lock,
+        } = &mut resources;
+
+        // バッジを更新（Persistentのみの場合は切り替え時に一時的に拡大）
+        let mut enlarged = false;
+        if let Some(badge) = badge.as_mut() {
+            enlarged = config.indicator.mode == IndicatorMode::Persistent
+                && !reduced_motion
+                && badge.is_showing()
+                && badge.can_enlarge();
+            if let Err(e) = badge.show(&mut wayland, &text, enlarged) {
+                error!("バッジ表示エラー: {}", "Badge display error: {}", e);
+            }
+            // バッジのみの場合はバッジの更新が最初のフレーム
+            if !config.indicator.mode.flash() {
+                trace.mark(Stage::Committed);
+            }
+        }
+
+        if config.indicator.mode.flash() {
+            // オーバーレイを表示（キャッシュされたバッファを使用）
+            if let Err(e) = show_overlay_cached(
+                &mut wayland,
+                overlay_caches,
+                &text,
+                config,
+                reduced_motion,
+                sources,
+                session_lock.as_ref(),
+                &mut trace,
+            ) {
+                error!("表示エラー: {}", "Display error: {}", e);
+            }
+        }
+        trace.finish();
+
+        // 拡大表示から通常サイズに戻す
+        if let (Some(badge), true) = (badge.as_mut(), enlarged) {
+            std::thread::sleep(Duration::from_millis(config.indicator.badge.enlarge_duration_ms));
+            if let Err(e) = badge.restore(&mut wayland) {
+                error!("バッジ表示エラー: {}", "Badge display error: {}", e);
+            }
+        }
+
+        // 表示のエラーが接続の切断によるものなら再接続させる
+        wayland.check_connection()?;
+    }
+
+    // バッジを片付けてから終了
+    resources.destroy();
+    wayland.conn.flush()?;
+
+    Ok(())
+}
+
+/// 設定から作成する表示スレッドの資源（設定の再読み込み時に作り直す）
+struct DisplayResources {
+    overlay_caches: OverlayCaches,
+    badge: Option<Badge>,
+    session_lock: Option<SessionLock>,
+}
+
+impl DisplayResources {
+    fn new(
+        wayland: &mut WaylandContext,
+        config: &Config,
+        sources: &PlacementSources,
+        paused: &AtomicBool,
+    ) -> Result<Self> {
+        // バッファキャッシュを作成（出力のバッファスケールごと）
+        let mut overlay_caches = OverlayCaches::default();
+        let scales: Vec<i32> = wayland
+            .target_outputs(&config.placement.outputs)
+            .iter()
+            .map(|target| target.scale)
+            .collect();
+
+        // 設定ファイルの入力メソッドを事前レンダリング
+        for scale in scales {
+            for display_text in config.input_method_names.values() {
+                overlay_caches.prerender(&config.overlay, None, scale, display_text)?;
+                debug!("バッファを事前レンダリング: {} (x{})", "Pre-rendered buffer: {} (x{})", display_text, scale);
+            }
+        }
+
+        // 画面ロックの検出（ロック中も通常どおり表示する設定では接続しない）
+        let session_lock = (config.suppress.locked != SuppressAction::Show).then(SessionLock::connect);
+
+        // 常時表示バッジ（Persistent / Bothの場合のみ）
+        let badge = if config.indicator.mode.persistent() {
+            Some(Badge::new(wayland, config, &sources.windows)?)
+        } else {
+            None
+        };
+
+        let mut resources = Self {
+            overlay_caches,
+            badge,
+            session_lock,
+        };
+        resources.update_badge_visibility(wayland, config, paused)?;
+        Ok(resources)
+    }
+
+    /// 一時停止中と、画面ロック中に表示しない設定でのロック中はバッジを隠す
+    fn update_badge_visibility(
+        &mut self,
+        wayland: &mut WaylandContext,
+        config: &Config,
+        paused: &AtomicBool,
+    ) -> Result<()> {
+        let Some(badge) = self.badge.as_mut() else {
+            return Ok(());
+        };
+        let locked = self.session_lock.as_ref().is_some_and(SessionLock::is_locked);
+        let hidden = paused.load(Ordering::Relaxed) || (locked && config.suppress.locked == SuppressAction::Hide);
+        badge.set_hidden(wayland, hidden)
+    }
+
+    /// バッジのサーフェスを破棄
+    fn destroy(self) {
+        if let Some(badge) = self.badge {
+            badge.destroy();
+        }
+    }
+}
@@ -0,0 +1,55 @@
+// フォーカス変化時の表示
+// fcitx5はウィンドウごとに入力状態を持つため、切り替え先の入力メソッドを表示する
+
+use std::time::Duration;
+
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+
+use crate::compositor::ActiveWindow;
+use crate::input_method::get_current_input_method;
+use crate::latency::{Stage, Trace};
+use crate::service::DaemonState;
+
+/// フォーカス変化を受け取って表示リクエストを送るスレッドを起動
+pub fn spawn_focus_trigger(focus_rx: Receiver<ActiveWindow>, state: DaemonState) {
+    std::thread::spawn(move || {
+        while let Ok(mut window) = focus_rx.recv() {
+            // フォーカス変化時点の入力メソッド（変更シグナル側で表示済みかの判定用）
+            let im_at_focus = state.last_input_method.lock().unwrap().clone();
+            let config = state.config.get();
+            let debounce = Duration::from_millis(config.focus_trigger.debounce_ms);
+
+            // デバウンス: 連続したフォーカス変化は最後のウィンドウだけ扱う
+            loop {
+                match focus_rx.recv_timeout(debounce) {
+                    Ok(next) => window = next,
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+
+            // 再読み込みで無効にされた場合も含め、無効・除外対象なら表示しない
+            if !config.focus_trigger.enabled || config.focus_trigger.exclude_classes.contains(&window.class) {
+                continue;
+            }
+
+            let mut trace = Trace::start();
+            let Ok(current) = get_current_input_method() else {
+                continue;
+            };
+            trace.mark(Stage::Queried);
+
+            let mut last = state.last_input_method.lock().unwrap();
+            // 入力メソッドも変わっていた場合は変更シグナル側で表示済み
+            if *last != im_at_focus {
+                continue;
+            }
+            debug!("フォーカス変化: {} ({})", "Focus changed: {} ({})", window.class, current);
+            *last = current.clone();
+
+            if !state.is_paused() {
+                state.show_input_method(&current, trace);
+            }
+        }
+    });
+}
@@ -0,0 +1,142 @@
+// フォント名の解決（fontconfig）
+// Cairoは見つからないフォントを黙って代替するため、設定の検証で事前に確認する
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+
+use fontconfig_sys::constants::FC_FAMILY;
+use fontconfig_sys::{
+    FcConfigSubstitute, FcDefaultSubstitute, FcFontMatch, FcMatchPattern, FcPatternAddString,
+    FcPatternCreate, FcPatternDestroy, FcPatternGetString, FcResultMatch,
+};
+
+/// fontconfigの設定で別名として定義されている総称ファミリー
+const GENERIC_FAMILIES: &[&str] = &[
+    "sans-serif",
+    "sans",
+    "serif",
+    "monospace",
+    "mono",
+    "cursive",
+    "fantasy",
+    "system-ui",
+    "emoji",
+    "math",
+];
+
+/// フォントファミリーが見つからない場合に代わりに使われるファミリー
+///
+/// 指定したファミリー（または総称ファミリー）で解決できる場合はNone。
+/// フォントが1つも無い場合は空文字列を返す
+pub fn fallback(family: &str) -> Option<String> {
+    if GENERIC_FAMILIES.iter().any(|generic| same_family(generic, family)) {
+        return None;
+    }
+    let families = matched_families(family);
+    if families.iter().any(|matched| same_family(matched, family)) {
+        return None;
+    }
+    Some(families.into_iter().next().unwrap_or_default())
+}
+
+/// Cairoと同じ手順（置換規則の適用後に最も近いフォント）で選ばれるフォントのファミリー名
+fn matched_families(family: &str) -> Vec<String> {
+    let Ok(family) = CString::new(family) else {
+        return Vec::new();
+    };
+
+    let mut families = Vec::new();
+    // 設定はNULL（fontconfigの現在の設定、未初期化なら初期化される）を使う
+    unsafe {
+        let pattern = FcPatternCreate();
+        if pattern.is_null() {
+            return families;
+        }
+        FcPatternAddString(pattern, FC_FAMILY.as_ptr(), family.as_ptr().cast());
+        FcConfigSubstitute(ptr::null_mut(), pattern, FcMatchPattern);
+        FcDefaultSubstitute(pattern);
+
+        let mut result = 0;
+        let matched = FcFontMatch(ptr::null_mut(), pattern, &mut result);
+        FcPatternDestroy(pattern);
+        if matched.is_null() {
+            return families;
+        }
+
+        // フォントは言語ごとに複数のファミリー名を持つことがある
+        let mut index = 0;
+        let mut name = ptr::null_mut();
+        while FcPatternGetString(matched, FC_FAMILY.as_ptr(), index, &mut name) == FcResultMatch {
+            families.push(CStr::from_ptr(name as *const c_char).to_string_lossy().into_owned());
+            index += 1;
+        }
+        FcPatternDestroy(matched);
+    }
+    families
+}
+
+/// ファミリー名の比較（fontconfigと同じく大文字・小文字と空白を区別しない）
+fn same_family(a: &str, b: &str) -> bool {
+    let normalize = |name: &str| {
+        name.chars()
+            .filter(|c| !c.is_whitespace())
+            .flat_map(char::to_lowercase)
+            .collect::<String>()
+    };
+    normalize(a) == normalize(b)
+}
+
+/// テスト用: fontconfigの現在の設定を、同梱のテスト用フォントだけを使う設定に置き換える
+///
+/// 環境変数を書き換えずにテストのプロセス全体で同じフォントを使うため、
+/// フォントを使うテストのヘルパー（描画・フォント名の解決・設定の検証）から呼ぶ
+#[cfg(test)]
+pub(crate) fn use_test_fonts() {
+    use fontconfig_sys::{FcConfigBuildFonts, FcConfigCreate, FcConfigParseAndLoad, FcConfigSetCurrent};
+
+    static ONCE: std::sync::Once = std::sync::Once::new();
+    ONCE.call_once(|| {
+        let fonts_conf = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/fonts/fonts.conf\0");
+        // 設定はプロセスの終了まで使うので解放しない
+        unsafe {
+            let config = FcConfigCreate();
+            assert!(!config.is_null(), "fontconfigの設定を作成できません");
+            assert!(
+                FcConfigParseAndLoad(config, fonts_conf.as_ptr(), 1) != 0
+                    && FcConfigBuildFonts(config) != 0
+                    && FcConfigSetCurrent(config) != 0,
+                "テスト用フォントの設定を読み込めません: {}",
+                fonts_conf.trim_end_matches('\0')
+            );
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fallback(family: &str) -> Option<String> {
+        use_test_fonts();
+        super::fallback(family)
+    }
+
+    #[test]
+    fn bundled_font_resolves() {
+        assert_eq!(fallback("DejaVu Sans"), None);
+        assert_eq!(fallback("dejavusans"), None);
+        assert_eq!(fallback("sans-serif"), None);
+    }
+
+    #[test]
+    fn missing_font_falls_back() {
+        assert_eq!(fallback("No Such Font").as_deref(), Some("DejaVu Sans"));
+    }
+
+    #[test]
+    fn system_fonts_are_not_used_in_tests() {
+        // システムにあっても同梱していないフォントは見つからない
+        assert_eq!(fallback("DejaVu Serif").as_deref(), Some("DejaVu Sans"));
+    }
+}
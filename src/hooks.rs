@@ -0,0 +1,131 @@
+// 入力メソッド変更時のフック（ユーザー指定のコマンドを実行）
+// DBusループや表示スレッドを止めないよう専用スレッドで順に実行する
+
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{unbounded, Sender};
+use nix::sys::signal::{killpg, Signal};
+use nix::unistd::Pid;
+
+use crate::config::SharedConfig;
+
+/// 終了を確認する間隔
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// タイムアウト時にSIGTERMを送ってからSIGKILLを送るまでの猶予
+const KILL_GRACE_PERIOD: Duration = Duration::from_millis(500);
+
+/// フックに渡す入力メソッドの変更
+pub struct HookEvent {
+    /// 変更前の入力メソッド（起動時は空）
+    pub old: String,
+    pub new: String,
+    /// 表示テキスト
+    pub label: String,
+    /// アクティブウィンドウのクラス（取得できない場合は空）
+    pub window_class: String,
+}
+
+/// フックを実行するスレッドを起動
+///
+/// フックの実行中に溜まった変更はまとめて1回として扱う（IM_OLDは最初の変更前、それ以外は最新）
+pub fn spawn_hook_runner(config: SharedConfig) -> Sender<HookEvent> {
+    let (tx, rx) = unbounded::<HookEvent>();
+
+    std::thread::spawn(move || {
+        while let Ok(mut event) = rx.recv() {
+            // 遅れている間の古い変更は実行しない
+            for newer in rx.try_iter() {
+                event = HookEvent { old: event.old, ..newer };
+            }
+            if event.old == event.new {
+                continue;
+            }
+
+            let config = config.get();
+            let timeout = Duration::from_millis(config.hooks.timeout_ms);
+
+            for command in &config.hooks.on_change {
+                let child = Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .env("IM_OLD", &event.old)
+                    .env("IM_NEW", &event.new)
+                    .env("IM_LABEL", &event.label)
+                    .env("WINDOW_CLASS", &event.window_class)
+                    .stdin(Stdio::null())
+                    // タイムアウト時にshから起動されたプロセスもまとめて終了させる
+                    .process_group(0)
+                    .spawn();
+
+                match child {
+                    Ok(child) => wait_with_timeout(child, command, timeout),
+                    Err(e) => warn!("フックを実行できません: {}: {}", "Cannot run hook: {}: {}", command, e),
+                }
+            }
+        }
+    });
+
+    tx
+}
+
+/// 終了を待つ（タイムアウトした場合はプロセスグループごと終了させる）
+fn wait_with_timeout(mut child: Child, command: &str, timeout: Duration) {
+    match wait_until(&mut child, Instant::now() + timeout) {
+        Ok(Some(status)) => {
+            if !status.success() {
+                warn!("フックが失敗しました（{}）: {}", "Hook failed ({}): {}", status, command);
+            }
+        }
+        Ok(None) => {
+            warn!("フックがタイムアウトしたため終了させます: {}", "Hook timed out, killing it: {}", command);
+            // spawnでプロセスグループを作ったので、グループIDはshのPID
+            let group = Pid::from_raw(child.id() as i32);
+            killpg(group, Signal::SIGTERM).ok();
+            if !matches!(wait_until(&mut child, Instant::now() + KILL_GRACE_PERIOD), Ok(Some(_))) {
+                killpg(group, Signal::SIGKILL).ok();
+                child.wait().ok();
+            }
+            // shが先に終了しても、SIGTERMを無視したプロセスが残っていれば終了させる
+            killpg(group, Signal::SIGKILL).ok();
+        }
+        Err(e) => {
+            warn!("フックの終了を確認できません: {}: {}", "Cannot check whether hook exited: {}: {}", command, e);
+        }
+    }
+}
+
+/// 期限まで終了を待つ（終了しなかった場合はNone）
+fn wait_until(child: &mut Child, deadline: Instant) -> std::io::Result<Option<ExitStatus>> {
+    loop {
+        match child.try_wait()? {
+            Some(status) => return Ok(Some(status)),
+            None if Instant::now() >= deadline => return Ok(None),
+            None => std::thread::sleep(POLL_INTERVAL),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_kills_processes_started_by_the_hook() {
+        let pid_file = std::env::temp_dir().join(format!("hook-timeout-{}", std::process::id()));
+        let command = format!("sleep 30 & echo $! > {}; wait", pid_file.display());
+        let child = Command::new("sh").arg("-c").arg(&command).process_group(0).spawn().unwrap();
+
+        let start = Instant::now();
+        wait_with_timeout(child, &command, Duration::from_millis(200));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        std::fs::remove_file(&pid_file).ok();
+        // 終了していれば、回収されるまではゾンビ（状態Z）として残る
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid.trim())).unwrap_or_default();
+        let state = stat.rsplit(')').next().unwrap_or_default().split_whitespace().next();
+        assert!(matches!(state, None | Some("Z")), "sleep {} is still running: {}", pid.trim(), stat);
+    }
+}
@@ -0,0 +1,86 @@
+// fcitx5の入力メソッドの取得と変更の監視（DBus）
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use dbus::blocking::Connection as DbusConnection;
+use dbus::message::MatchRule;
+
+use crate::latency::{Stage, Trace};
+
+/// fcitx5の現在の入力メソッドをDBusで取得
+///
+/// fcitx5が起動していない場合はエラー
+pub fn get_current_input_method() -> Result<String> {
+    let conn = dbus::blocking::Connection::new_session()
+        .context("DBusセッションバスへの接続に失敗")?;
+
+    let proxy = conn.with_proxy(
+        "org.fcitx.Fcitx5",
+        "/controller",
+        Duration::from_millis(5000),
+    );
+
+    let (input_method,): (String,) = proxy.method_call(
+        "org.fcitx.Fcitx.Controller1",
+        "CurrentInputMethod",
+        (),
+    ).context("fcitx5から入力メソッドの取得に失敗")?;
+
+    Ok(input_method)
+}
+
+/// fcitx5のプロパティ変更シグナルを購読し、受け取るたびに現在の入力メソッドを`on_change`に渡す
+///
+/// `on_change`は`conn`の`process()`の中で呼ばれる。
+/// 入力メソッドが変わっていない場合も呼ばれるので、重複の除去は呼び出し側で行う。
+/// シグナルを送らない状況もあるため、定期的に[`get_current_input_method`]で確認するとより確実
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// let conn = dbus::blocking::Connection::new_session()?;
+/// wayland_fcitx5_indicator::input_method::watch(&conn, |input_method| {
+///     println!("{}", input_method);
+/// })?;
+/// loop {
+///     conn.process(Duration::from_millis(500))?;
+/// }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn watch<F>(conn: &DbusConnection, mut on_change: F) -> Result<()>
+where
+    F: FnMut(String) + Send + 'static,
+{
+    watch_traced(conn, move |input_method, _| on_change(input_method))
+}
+
+/// [`watch`]と同じく購読し、シグナルを受け取ってからの遅延の計測も渡す
+pub(crate) fn watch_traced<F>(conn: &DbusConnection, mut on_change: F) -> Result<()>
+where
+    F: FnMut(String, Trace) + Send + 'static,
+{
+    // fcitx5のプロパティ変更シグナルをマッチ
+    let rule = MatchRule::new_signal("org.fcitx.Fcitx.InputMethod1", "CurrentIMChanged");
+
+    conn.add_match(rule, move |_: (), _, _| {
+        // シグナル受信時の処理
+        true
+    }).context("マッチルールの追加に失敗")?;
+
+    // 代替案: PropertiesChangedシグナルも監視
+    let rule2 = MatchRule::new_signal("org.freedesktop.DBus.Properties", "PropertiesChanged")
+        .with_sender("org.fcitx.Fcitx5");
+
+    conn.add_match(rule2, move |_: (), _, _| {
+        // 入力メソッドが変更されたかチェック
+        let mut trace = Trace::start();
+        if let Ok(current) = get_current_input_method() {
+            trace.mark(Stage::Queried);
+            on_change(current, trace);
+        }
+        true
+    }).context("マッチルールの追加に失敗")?;
+
+    Ok(())
+}
@@ -0,0 +1,160 @@
+// 表示までの遅延の計測
+// 入力メソッドの変更を受け取ってから最初のフレームを送るまでの各段階の経過時間を記録する
+// 1回ごとの内訳はdebugログに、--statsでは終了時に段階ごとのp50/p95を表示する
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 段階ごとに保持する計測値の上限（古いものから捨てる）
+const MAX_SAMPLES: usize = 10_000;
+
+/// 計測する段階（起点からの経過時間を記録）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    /// DBusシグナル・フォーカス変化・DBusメソッド呼び出しを受け取った（起点）
+    Received,
+    /// get_current_input_methodが返った
+    Queried,
+    /// 表示スレッドへ送った
+    Sent,
+    /// 配置のためのジオメトリ（ウィンドウ・キャレットなど）を取得した
+    Geometry,
+    /// 全サーフェスのconfigureを受け取った
+    Configured,
+    /// 最初のバッファをコミットした（通知の場合は通知を送った）
+    Committed,
+}
+
+impl Stage {
+    const ALL: [Stage; 6] = [
+        Stage::Received,
+        Stage::Queried,
+        Stage::Sent,
+        Stage::Geometry,
+        Stage::Configured,
+        Stage::Committed,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Stage::Received => "received",
+            Stage::Queried => "query_im",
+            Stage::Sent => "send",
+            Stage::Geometry => "geometry",
+            Stage::Configured => "configure",
+            Stage::Committed => "commit",
+        }
+    }
+}
+
+/// 1回の表示の計測
+#[derive(Debug)]
+pub struct Trace {
+    start: Instant,
+    marks: Vec<(Stage, Duration)>,
+}
+
+impl Trace {
+    /// 計測を開始（受け取った時点を起点にする）
+    pub fn start() -> Self {
+        Self {
+            start: Instant::now(),
+            marks: vec![(Stage::Received, Duration::ZERO)],
+        }
+    }
+
+    /// 段階に達した時刻を記録（同じ段階は最初の1回のみ）
+    pub fn mark(&mut self, stage: Stage) {
+        if self.marks.iter().all(|(marked, _)| *marked != stage) {
+            self.marks.push((stage, self.start.elapsed()));
+        }
+    }
+
+    /// 計測を終えてログと統計に記録
+    pub fn finish(self) {
+        let breakdown: Vec<String> = self
+            .marks
+            .iter()
+            .map(|(stage, elapsed)| format!("{} {:.2}ms", stage.name(), millis(*elapsed)))
+            .collect();
+        debug!("表示までの遅延: {}", "Latency to display: {}", breakdown.join(", "));
+
+        if let Some(stats) = STATS.lock().unwrap().as_mut() {
+            stats.record(&self.marks);
+        }
+    }
+}
+
+/// 段階ごとの計測値
+#[derive(Default)]
+struct Stats {
+    samples: HashMap<Stage, VecDeque<Duration>>,
+}
+
+impl Stats {
+    fn record(&mut self, marks: &[(Stage, Duration)]) {
+        for (stage, elapsed) in marks {
+            let samples = self.samples.entry(*stage).or_default();
+            if samples.len() == MAX_SAMPLES {
+                samples.pop_front();
+            }
+            samples.push_back(*elapsed);
+        }
+    }
+}
+
+/// 統計（--statsの場合のみ収集）
+static STATS: Mutex<Option<Stats>> = Mutex::new(None);
+
+/// 統計の収集を開始（戻り値を破棄すると統計を標準出力に表示する）
+pub fn enable_stats() -> StatsReport {
+    *STATS.lock().unwrap() = Some(Stats::default());
+    StatsReport
+}
+
+/// 破棄されたときに統計を表示する（デーモンがどの経路で終了しても表示するため）
+pub struct StatsReport;
+
+impl Drop for StatsReport {
+    fn drop(&mut self) {
+        print_stats();
+    }
+}
+
+/// 段階ごとの件数とp50/p95（起点からの経過時間）を標準出力に表示
+fn print_stats() {
+    let stats = STATS.lock().unwrap();
+    let Some(stats) = stats.as_ref() else {
+        return;
+    };
+
+    println!("{:<10} {:>7} {:>10} {:>10}", "stage", "count", "p50", "p95");
+    for stage in Stage::ALL {
+        let Some(samples) = stats.samples.get(&stage) else {
+            continue;
+        };
+        let mut sorted: Vec<Duration> = samples.iter().copied().collect();
+        sorted.sort();
+        println!(
+            "{:<10} {:>7} {:>8.2}ms {:>8.2}ms",
+            stage.name(),
+            sorted.len(),
+            millis(percentile(&sorted, 0.50)),
+            millis(percentile(&sorted, 0.95)),
+        );
+    }
+}
+
+/// ソート済みの計測値のパーセンタイル（nearest-rank法）
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
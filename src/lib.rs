@@ -0,0 +1,53 @@
+//! fcitx5の入力メソッドをWaylandのオーバーレイで表示するライブラリ
+//!
+//! `wayland_fcitx5_indicator`コマンド（デーモン）の部品を、他のツールから使えるように公開する
+//!
+//! - [`Config`] — 設定ファイルの読み込み
+//! - [`render`] — テキストの描画（[`RenderStyle`]、ARGB8888のピクセルデータ、PNG）
+//! - [`BufferCache`] — 描画済みピクセルデータのキャッシュ
+//! - [`input_method`] — fcitx5の現在の入力メソッドの取得と変更の監視
+//! - [`Overlay`] — レイヤーシェルでのオーバーレイ表示
+//! - [`daemon`] — デーモン本体
+//!
+//! ```no_run
+//! use wayland_fcitx5_indicator::{input_method, Config, Overlay};
+//!
+//! let config = Config::load();
+//! let label = config.get_display_text(&input_method::get_current_input_method()?);
+//! let mut overlay = Overlay::new(config)?;
+//! overlay.show(&label)?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+// ログ出力のマクロ（error! / warn! / info! / debug!）を以降のモジュールで使うため最初に宣言
+#[macro_use]
+pub mod logging;
+mod accessibility;
+mod badge;
+mod buffer;
+mod caret;
+mod compositor;
+pub mod config;
+pub mod daemon;
+mod display;
+mod focus;
+mod fonts;
+mod hooks;
+pub mod input_method;
+mod latency;
+mod lifecycle;
+mod notification;
+mod overlay;
+mod placement;
+pub mod render;
+mod service;
+mod session;
+pub mod status;
+mod tray;
+mod validation;
+mod wayland;
+
+pub use buffer::BufferCache;
+pub use config::Config;
+pub use overlay::Overlay;
+pub use render::RenderStyle;
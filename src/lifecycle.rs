@@ -0,0 +1,98 @@
+// プロセスのライフサイクル（シグナル処理とsystemdへの通知）
+// systemdで起動されていない場合（NOTIFY_SOCKETが無い）は通知は何もしない
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use sd_notify::NotifyState;
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+
+/// 受け取ったシグナル（メインループで確認する）
+pub struct Signals {
+    /// SIGTERM / SIGINT（終了）
+    terminate: Arc<AtomicBool>,
+    /// SIGHUP（設定の再読み込み）
+    reload: Arc<AtomicBool>,
+}
+
+impl Signals {
+    /// シグナルハンドラを登録
+    pub fn register() -> Result<Self> {
+        let signals = Self {
+            terminate: Arc::new(AtomicBool::new(false)),
+            reload: Arc::new(AtomicBool::new(false)),
+        };
+
+        for signal in [SIGTERM, SIGINT] {
+            signal_hook::flag::register(signal, Arc::clone(&signals.terminate))
+                .context("シグナルハンドラの登録に失敗")?;
+        }
+        signal_hook::flag::register(SIGHUP, Arc::clone(&signals.reload))
+            .context("シグナルハンドラの登録に失敗")?;
+
+        Ok(signals)
+    }
+
+    /// 終了を求められたかどうか
+    pub fn terminate_requested(&self) -> bool {
+        self.terminate.load(Ordering::Relaxed)
+    }
+
+    /// 立てると終了を求められたことになるフラグ（シグナル以外の理由で終了する場合に使う）
+    pub fn terminate_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.terminate)
+    }
+
+    /// 再読み込みを求められたかどうか（確認するとリセットされる）
+    pub fn take_reload(&self) -> bool {
+        self.reload.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// 起動完了を通知（READY=1）
+pub fn notify_ready() {
+    sd_notify::notify(false, &[NotifyState::Ready]).ok();
+}
+
+/// 設定の再読み込みを通知（RELOADING=1、完了後にREADY=1）
+pub fn notify_reloading() {
+    if let Ok(now) = NotifyState::monotonic_usec_now() {
+        sd_notify::notify(false, &[NotifyState::Reloading, now]).ok();
+    }
+}
+
+/// 終了処理の開始を通知（STOPPING=1）
+pub fn notify_stopping() {
+    sd_notify::notify(false, &[NotifyState::Stopping]).ok();
+}
+
+/// systemdのウォッチドッグ（WatchdogSecが設定されている場合のみ有効）
+pub struct Watchdog {
+    /// 通知の間隔（タイムアウトの半分）
+    interval: Option<Duration>,
+    last_ping: Instant,
+}
+
+impl Watchdog {
+    pub fn new() -> Self {
+        let mut usec = 0;
+        let interval = sd_notify::watchdog_enabled(false, &mut usec).then(|| Duration::from_micros(usec) / 2);
+        Self {
+            interval,
+            last_ping: Instant::now(),
+        }
+    }
+
+    /// 前回の通知から間隔が空いていればWATCHDOG=1を送る
+    pub fn ping(&mut self) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+        if self.last_ping.elapsed() >= interval {
+            sd_notify::notify(false, &[NotifyState::Watchdog]).ok();
+            self.last_ping = Instant::now();
+        }
+    }
+}
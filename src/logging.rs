@@ -0,0 +1,173 @@
+// ログ出力
+// レベルによる絞り込み（RUST_LOG形式）、journald向けの出力、日本語・英語のメッセージ切り替え
+
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::config::{Config, LogLanguage, LogLevel, LogOutput, LoggingConfig};
+
+/// 言語の設定に応じて日本語か英語のメッセージでログを出力
+///
+/// 書式文字列は日本語・英語の順に指定し、引数は両方で共通
+#[doc(hidden)]
+#[macro_export]
+macro_rules! log_message {
+    ($level:expr, $ja:literal, $en:literal $(, $arg:expr)* $(,)?) => {
+        if $crate::logging::english() {
+            log::log!($level, $en $(, $arg)*)
+        } else {
+            log::log!($level, $ja $(, $arg)*)
+        }
+    };
+}
+
+/// 言語の設定に応じて日本語か英語の文字列を作る（ログ以外の利用者向けの表示用）
+///
+/// 書式文字列はlog_message!と同じく日本語・英語の順に指定する
+macro_rules! localized {
+    ($ja:literal, $en:literal $(, $arg:expr)* $(,)?) => {
+        if $crate::logging::english() {
+            format!($en $(, $arg)*)
+        } else {
+            format!($ja $(, $arg)*)
+        }
+    };
+}
+
+macro_rules! error {
+    ($($message:tt)*) => { log_message!(log::Level::Error, $($message)*) };
+}
+
+macro_rules! warn {
+    ($($message:tt)*) => { log_message!(log::Level::Warn, $($message)*) };
+}
+
+macro_rules! info {
+    ($($message:tt)*) => { log_message!(log::Level::Info, $($message)*) };
+}
+
+macro_rules! debug {
+    ($($message:tt)*) => { log_message!(log::Level::Debug, $($message)*) };
+}
+
+/// 英語でメッセージを出力するかどうか
+static ENGLISH: AtomicBool = AtomicBool::new(false);
+
+static LOGGER: Logger = Logger {
+    settings: RwLock::new(None),
+};
+
+struct Settings {
+    filter: env_filter::Filter,
+    journald: bool,
+}
+
+/// 標準エラー出力へのロガー（設定の再読み込みで絞り込みや出力形式を変えられる）
+struct Logger {
+    settings: RwLock<Option<Settings>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let settings = self.settings.read().unwrap();
+        settings.as_ref().is_some_and(|settings| settings.filter.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        let settings = self.settings.read().unwrap();
+        let Some(settings) = settings.as_ref() else {
+            return;
+        };
+        if !settings.filter.matches(record) {
+            return;
+        }
+
+        // journaldは時刻を自分で記録し、行頭の<N>を優先度として解釈する
+        let line = if settings.journald {
+            format!("<{}>{}\n", syslog_priority(record.level()), record.args())
+        } else {
+            format!(
+                "{} {:<5} {}\n",
+                humantime::format_rfc3339_millis(SystemTime::now()),
+                record.level(),
+                record.args()
+            )
+        };
+        // 1行ずつまとめて書き込み、スレッド間で行が混ざらないようにする
+        std::io::stderr().write_all(line.as_bytes()).ok();
+    }
+
+    fn flush(&self) {
+        std::io::stderr().flush().ok();
+    }
+}
+
+/// ロガーを登録（設定をロードする前のメッセージは既定の設定で出力）
+pub fn init() {
+    configure(&Config::built_in().logging);
+    log::set_logger(&LOGGER).ok();
+}
+
+/// ログの設定を適用（起動時と設定の再読み込み時）
+pub fn configure(config: &LoggingConfig) {
+    let mut builder = env_filter::Builder::new();
+    builder.filter_level(level_filter(config.level));
+    if let Ok(spec) = std::env::var("RUST_LOG") {
+        builder.parse(&spec);
+    }
+    let filter = builder.build();
+
+    let journald = match config.output {
+        LogOutput::Auto => std::env::var_os("JOURNAL_STREAM").is_some(),
+        LogOutput::Terminal => false,
+        LogOutput::Journald => true,
+    };
+    let english = match config.language {
+        LogLanguage::Auto => !locale_is_japanese(),
+        LogLanguage::Japanese => false,
+        LogLanguage::English => true,
+    };
+
+    ENGLISH.store(english, Ordering::Relaxed);
+    log::set_max_level(filter.filter());
+    *LOGGER.settings.write().unwrap() = Some(Settings { filter, journald });
+}
+
+/// メッセージを英語で出力するかどうか
+pub fn english() -> bool {
+    ENGLISH.load(Ordering::Relaxed)
+}
+
+fn level_filter(level: LogLevel) -> LevelFilter {
+    match level {
+        LogLevel::Off => LevelFilter::Off,
+        LogLevel::Error => LevelFilter::Error,
+        LogLevel::Warn => LevelFilter::Warn,
+        LogLevel::Info => LevelFilter::Info,
+        LogLevel::Debug => LevelFilter::Debug,
+        LogLevel::Trace => LevelFilter::Trace,
+    }
+}
+
+/// syslogの優先度（sd-daemon(3)の接頭辞）
+fn syslog_priority(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+/// メッセージのロケールが日本語かどうか（最初に設定されている変数で判定）
+fn locale_is_japanese() -> bool {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .is_some_and(|value| value.starts_with("ja"))
+}
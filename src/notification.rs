@@ -0,0 +1,77 @@
+// デスクトップ通知での表示（レイヤーシェルが使えないコンポジタ向け）
+// org.freedesktop.Notificationsに表示テキストを送り、前の通知を置き換える
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use crossbeam_channel::{Receiver, Sender};
+use dbus::arg::{PropMap, Variant};
+use dbus::blocking::Connection as DbusConnection;
+
+use crate::config::SharedConfig;
+use crate::display::DisplayRequest;
+use crate::latency::Stage;
+
+/// 表示リクエストを通知として送り続ける（表示スレッドの代わりに実行）
+///
+/// DBusに接続できたらready_txに通知する
+pub fn run(rx: &Receiver<DisplayRequest>, config: &SharedConfig, ready_tx: &Sender<()>) -> Result<()> {
+    let conn = DbusConnection::new_session()
+        .context("DBusセッションバスへの接続に失敗")?;
+    let proxy = conn.with_proxy(
+        "org.freedesktop.Notifications",
+        "/org/freedesktop/Notifications",
+        Duration::from_millis(2000),
+    );
+
+    info!("デスクトップ通知で表示します", "Showing input methods as desktop notifications");
+    ready_tx.send(()).ok();
+
+    // 直前の通知のID（置き換えて通知が積み重ならないようにする）
+    let mut notification_id = 0u32;
+
+    loop {
+        let (text, mut trace) = match rx.recv() {
+            Ok(DisplayRequest::Show(text, trace)) => (text, trace),
+            // 通知では常時表示バッジを使わない
+            Ok(DisplayRequest::PauseChanged) => continue,
+            Ok(DisplayRequest::Shutdown) | Err(_) => break,
+        };
+        let config = config.get();
+        let expire_timeout = config.animation.display_duration_ms + config.animation.fade_duration_ms;
+
+        // 通知履歴に残さない一時的な通知
+        let mut hints = PropMap::new();
+        hints.insert("transient".into(), Variant(Box::new(true)));
+        hints.insert(
+            "x-canonical-private-synchronous".into(),
+            Variant(Box::new(env!("CARGO_PKG_NAME").to_string())),
+        );
+
+        let reply: Result<(u32,), _> = proxy.method_call(
+            "org.freedesktop.Notifications",
+            "Notify",
+            (
+                env!("CARGO_PKG_NAME"),
+                notification_id,
+                "input-keyboard",
+                text.as_str(),
+                "",
+                Vec::<String>::new(),
+                hints,
+                i32::try_from(expire_timeout).unwrap_or(i32::MAX),
+            ),
+        );
+
+        match reply {
+            Ok((id,)) => {
+                notification_id = id;
+                trace.mark(Stage::Committed);
+            }
+            Err(e) => warn!("通知の送信に失敗: {}", "Failed to send notification: {}", e),
+        }
+        trace.finish();
+    }
+
+    Ok(())
+}
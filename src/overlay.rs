@@ -0,0 +1,372 @@
+// オーバーレイの表示（レイヤーシェルのサーフェスにテキストを表示してフェードアウト）
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use wayland_client::{Connection, EventQueue};
+use wayland_client::protocol::wl_surface;
+use wayland_protocols_wlr::layer_shell::v1::client::{
+    zwlr_layer_shell_v1,
+    zwlr_layer_surface_v1::{KeyboardInteractivity, ZwlrLayerSurfaceV1},
+};
+
+use crate::buffer::BufferCache;
+use crate::caret::CaretTracker;
+use crate::compositor::WindowTracker;
+use crate::config::{
+    Config, OutputSelection, OverlayConfig, PlacementMode, ReducedMotion, SuppressAction, SuppressConfig,
+};
+use crate::latency::{Stage, Trace};
+use crate::placement::{self, PlacementSources};
+use crate::render::RenderStyle;
+use crate::session::SessionLock;
+use crate::wayland::{create_buffer_from_pixels, AppState, WaylandContext};
+
+/// 入力メソッドのオーバーレイ
+///
+/// 設定の`overlay`・`animation`・`placement`・`suppress`・`rules`に従って、
+/// デーモンと同じようにレイヤーシェルのサーフェスに表示する。
+/// 描画したテキストはキャッシュし、2回目以降は描画を省く。
+/// ウィンドウ・キャレットの監視スレッドは、破棄すると終了する
+///
+/// ```no_run
+/// use wayland_fcitx5_indicator::{Config, Overlay};
+///
+/// let mut overlay = Overlay::new(Config::load())?;
+/// overlay.show("あ")?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct Overlay {
+    wayland: WaylandContext,
+    caches: OverlayCaches,
+    config: Config,
+    sources: PlacementSources,
+    session_lock: Option<SessionLock>,
+}
+
+impl Overlay {
+    /// 環境変数（WAYLAND_DISPLAY・HYPRLAND_INSTANCE_SIGNATURE）で指定されたコンポジタに接続
+    ///
+    /// コンポジタがzwlr_layer_shell_v1に対応していない場合はエラー
+    pub fn new(config: Config) -> Result<Self> {
+        let hyprland_instance = std::env::var("HYPRLAND_INSTANCE_SIGNATURE").ok();
+        Self::with_wayland(WaylandContext::connect()?, config, hyprland_instance.as_deref())
+    }
+
+    /// 確立済みのWayland接続を使う（呼び出し側のツールと接続を共有する場合）
+    ///
+    /// `hyprland_instance`はウィンドウ・モニタ情報を取得するHyprlandのインスタンス
+    /// （HYPRLAND_INSTANCE_SIGNATUREの値）。Noneの場合は配置をレイヤーシェルのアンカーに任せる
+    pub fn from_connection(conn: Connection, config: Config, hyprland_instance: Option<&str>) -> Result<Self> {
+        Self::with_wayland(WaylandContext::from_connection(conn)?, config, hyprland_instance)
+    }
+
+    fn with_wayland(wayland: WaylandContext, config: Config, hyprland_instance: Option<&str>) -> Result<Self> {
+        // キャレット配置の場合はfcitx5の入力コンテキストを監視（できなければウィンドウ中央）
+        let caret = if config.placement.mode == PlacementMode::Caret {
+            CaretTracker::spawn().ok()
+        } else {
+            None
+        };
+        let sources = PlacementSources {
+            caret,
+            windows: WindowTracker::spawn(hyprland_instance, None),
+        };
+        let session_lock = (config.suppress.locked != SuppressAction::Show).then(SessionLock::connect);
+
+        Ok(Self {
+            wayland,
+            caches: OverlayCaches::default(),
+            config,
+            sources,
+            session_lock,
+        })
+    }
+
+    /// テキストを表示（表示時間とフェードアウトが終わるまで戻らない）
+    ///
+    /// アニメーション削減は`On`の場合のみ適用する（`Auto`ではデスクトップ設定を読まない）
+    pub fn show(&mut self, text: &str) -> Result<()> {
+        let reduced_motion = self.config.animation.reduced_motion == ReducedMotion::On;
+        show_overlay_cached(
+            &mut self.wayland,
+            &mut self.caches,
+            text,
+            &self.config,
+            reduced_motion,
+            &self.sources,
+            self.session_lock.as_ref(),
+            &mut Trace::start(),
+        )?;
+        self.wayland.check_connection()
+    }
+}
+
+/// イージング関数（ease-out cubic）
+fn ease_out_cubic(t: f64) -> f64 {
+    let t1 = t - 1.0;
+    t1 * t1 * t1 + 1.0
+}
+
+/// オーバーレイのバッファキャッシュ（見た目を上書きするルールと出力のバッファスケールごと）
+#[derive(Default)]
+pub(crate) struct OverlayCaches {
+    caches: HashMap<(Option<usize>, i32), BufferCache>,
+}
+
+impl OverlayCaches {
+    /// テキストを事前レンダリング（キャッシュが無ければ作成）
+    pub(crate) fn prerender(
+        &mut self,
+        overlay: &OverlayConfig,
+        rule: Option<usize>,
+        scale: i32,
+        text: &str,
+    ) -> Result<()> {
+        self.caches
+            .entry((rule, scale))
+            .or_insert_with(|| {
+                BufferCache::new(
+                    overlay.width as i32 * scale,
+                    overlay.height as i32 * scale,
+                    RenderStyle::overlay(overlay, scale),
+                )
+            })
+            .prerender(text)
+    }
+
+    pub(crate) fn get(&self, rule: Option<usize>, scale: i32) -> Option<&BufferCache> {
+        self.caches.get(&(rule, scale))
+    }
+}
+
+/// フルスクリーン・画面ロックの状態から表示方法を決める（該当するうち最も強い抑制を採用）
+fn suppress_action(suppress: &SuppressConfig, fullscreen: bool, locked: bool) -> SuppressAction {
+    let actions = [(fullscreen, suppress.fullscreen), (locked, suppress.locked)];
+    let applicable = || actions.iter().filter(|(active, _)| *active).map(|(_, action)| *action);
+
+    if applicable().any(|action| action == SuppressAction::Hide) {
+        SuppressAction::Hide
+    } else if applicable().any(|action| action == SuppressAction::Demote) {
+        SuppressAction::Demote
+    } else {
+        SuppressAction::Show
+    }
+}
+
+/// 表示中にウィンドウの移動を確認する間隔
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(16);
+
+/// 出力ごとのオーバーレイサーフェス
+struct OverlaySurface {
+    surface: wl_surface::WlSurface,
+    layer_surface: ZwlrLayerSurfaceV1,
+    scale: i32,
+}
+
+/// オーバーレイを表示（キャッシュされたバッファを使用）
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn show_overlay_cached(
+    wayland: &mut WaylandContext,
+    overlay_caches: &mut OverlayCaches,
+    text: &str,
+    config: &Config,
+    reduced_motion: bool,
+    sources: &PlacementSources,
+    session_lock: Option<&SessionLock>,
+    trace: &mut Trace,
+) -> Result<()> {
+    // フルスクリーン・画面ロック中は抑制するか、フルスクリーンやロック画面の下（Topレイヤー）に表示
+    let window = sources.windows.active_window();
+    let fullscreen = window.as_ref().is_some_and(|window| window.fullscreen);
+    let locked = session_lock.is_some_and(SessionLock::is_locked);
+    let layer = match suppress_action(&config.suppress, fullscreen, locked) {
+        SuppressAction::Show => zwlr_layer_shell_v1::Layer::Overlay,
+        SuppressAction::Demote => zwlr_layer_shell_v1::Layer::Top,
+        SuppressAction::Hide => {
+            debug!("フルスクリーン・画面ロック中のため表示を抑制", "Suppressed while fullscreen or locked");
+            return Ok(());
+        }
+    };
+
+    // アプリケーションごとのルールを評価（サーフェスを作る前に抑制・上書きを決める）
+    let rule = window
+        .as_ref()
+        .and_then(|window| config.matching_rule(&window.class, &window.title));
+    let rule_config;
+    let (config, style_rule) = match rule {
+        Some((_, rule)) if rule.suppress => {
+            debug!(
+                "ルールにより表示を抑制: {}",
+                "Suppressed by rule: {}",
+                window.map(|window| window.class).unwrap_or_default()
+            );
+            return Ok(());
+        }
+        Some((index, rule)) => {
+            rule_config = config.with_rule(rule);
+            (&rule_config, rule.overrides_appearance().then_some(index))
+        }
+        None => (config, None),
+    };
+
+    // 出力の追加・削除を反映して表示先を決定
+    if config.placement.outputs != OutputSelection::Focused {
+        wayland.sync_outputs()?;
+    }
+    let targets = wayland.target_outputs(&config.placement.outputs);
+
+    // 未キャッシュのテキストは動的にレンダリング
+    for target in &targets {
+        let cached = overlay_caches
+            .get(style_rule, target.scale)
+            .and_then(|buffer_cache| buffer_cache.get(text, 1.0))
+            .is_some();
+        if !cached {
+            overlay_caches.prerender(&config.overlay, style_rule, target.scale, text)?;
+            debug!("バッファを動的レンダリング: {} (x{})", "Rendered buffer on demand: {} (x{})", text, target.scale);
+        }
+    }
+
+    let WaylandContext {
+        conn,
+        event_queue,
+        qh,
+        compositor,
+        shm,
+        layer_shell,
+        ..
+    } = wayland;
+    let width = config.overlay.width;
+    let height = config.overlay.height;
+
+    // 設定に従って配置（アクティブウィンドウ・出力・固定位置・キャレット・ポインタ）
+    let output_names: Vec<Option<String>> = targets.iter().map(|target| target.name.clone()).collect();
+    let placements = placement::compute(config, sources, &output_names);
+    trace.mark(Stage::Geometry);
+
+    // 入力リージョンを空に設定（全サーフェスで共有）
+    let region = compositor.create_region(qh, ());
+
+    // サーフェスの作成（毎回新規作成、出力ごとに1つ）
+    let surfaces: Vec<OverlaySurface> = targets
+        .iter()
+        .zip(placements)
+        .map(|(target, placement)| {
+            let surface = compositor.create_surface(qh, ());
+            let layer_surface = layer_shell.get_layer_surface(
+                &surface,
+                target.output.as_ref(),
+                layer,
+                "modal_ime_indicator".to_string(),
+                qh,
+                (),
+            );
+
+            layer_surface.set_size(width, height);
+
+            let (margin_top, margin_right, margin_bottom, margin_left) = placement.margin;
+            layer_surface.set_anchor(placement.anchor);
+            layer_surface.set_margin(margin_top, margin_right, margin_bottom, margin_left);
+
+            layer_surface.set_keyboard_interactivity(KeyboardInteractivity::None);
+            layer_surface.set_exclusive_zone(-1);
+
+            surface.set_input_region(Some(&region));
+            surface.set_buffer_scale(target.scale);
+            surface.commit();
+
+            OverlaySurface {
+                surface,
+                layer_surface,
+                scale: target.scale,
+            }
+        })
+        .collect();
+    region.destroy();
+
+    // configure待機（全サーフェス分）
+    let mut state = AppState::new();
+    while state.configured < surfaces.len() {
+        event_queue.blocking_dispatch(&mut state)?;
+    }
+    trace.mark(Stage::Configured);
+
+    // 指定アルファ値のバッファを全サーフェスにアタッチ
+    let attach_all = |alpha: f64| -> Result<()> {
+        for overlay in &surfaces {
+            let Some(buffer_cache) = overlay_caches.get(style_rule, overlay.scale) else {
+                continue;
+            };
+            if let Some(pixels) = buffer_cache.get(text, alpha) {
+                let (buffer_width, buffer_height) = (buffer_cache.width(), buffer_cache.height());
+                let buffer = create_buffer_from_pixels(shm, qh, buffer_width, buffer_height, &pixels)?;
+                overlay.surface.attach(Some(&buffer), 0, 0);
+                overlay.surface.damage_buffer(0, 0, buffer_width, buffer_height);
+                overlay.surface.commit();
+            }
+        }
+        Ok(())
+    };
+
+    // 初期表示（キャッシュからピクセルデータを取得）
+    // flush: 非同期送信で即座に表示（5-10ms → <1ms）
+    attach_all(1.0)?;
+    conn.flush()?;
+    trace.mark(Stage::Committed);
+
+    // キャッシュの矩形が古い場合に備え、表示した後で問い合わせ直す（変わっていれば下で追従する）
+    sources.windows.refresh_active_window();
+
+    // 表示中にウィンドウが動いたら追従する
+    let mut generation = sources.windows.generation();
+    let mut follow_window = |event_queue: &mut EventQueue<AppState>, state: &mut AppState| -> Result<()> {
+        if sources.windows.generation() == generation {
+            return Ok(());
+        }
+        generation = sources.windows.generation();
+
+        let placements = placement::compute(config, sources, &output_names);
+        for (overlay, placement) in surfaces.iter().zip(placements) {
+            let (margin_top, margin_right, margin_bottom, margin_left) = placement.margin;
+            overlay.layer_surface.set_anchor(placement.anchor);
+            overlay.layer_surface.set_margin(margin_top, margin_right, margin_bottom, margin_left);
+            overlay.surface.commit();
+        }
+        event_queue.roundtrip(state)?;
+        Ok(())
+    };
+
+    // 表示時間
+    let display_end = Instant::now() + Duration::from_millis(config.animation.display_duration_ms);
+    while let Some(remaining) = display_end.checked_duration_since(Instant::now()) {
+        std::thread::sleep(remaining.min(FOLLOW_INTERVAL));
+        follow_window(event_queue, &mut state)?;
+    }
+
+    // フェードアウトアニメーション（アニメーション削減時は即座に消す）
+    let total_frames = if reduced_motion { 0 } else { config.animation.fade_frames };
+    let frame_duration = Duration::from_millis(
+        config.animation.fade_duration_ms / total_frames.max(1) as u64,
+    );
+
+    for frame in 1..=total_frames {
+        let t = frame as f64 / total_frames as f64;
+        let alpha = 1.0 - ease_out_cubic(t);
+
+        attach_all(alpha)?;
+        event_queue.roundtrip(&mut state)?;
+        std::thread::sleep(frame_duration);
+        follow_window(event_queue, &mut state)?;
+    }
+
+    // クリーンアップ
+    for overlay in surfaces {
+        overlay.layer_surface.destroy();
+        overlay.surface.destroy();
+    }
+    conn.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,248 @@
+// オーバーレイの配置計算（レイヤーサーフェスのアンカーとマージン）
+
+use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_surface_v1::Anchor;
+
+use crate::caret::{CaretRect, CaretTracker};
+use crate::compositor::{MonitorRect, WindowTracker};
+use crate::config::{Config, PlacementAnchor, PlacementMode};
+
+/// 論理座標での矩形
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    /// この矩形内にanchorの位置でサイズ(width, height)を置いた場合の左上座標
+    fn anchored(&self, anchor: PlacementAnchor, margin: i32, width: i32, height: i32) -> (i32, i32) {
+        let left = self.x + margin;
+        let right = self.x + self.width - width - margin;
+        let center_x = self.x + (self.width - width) / 2;
+        let top = self.y + margin;
+        let bottom = self.y + self.height - height - margin;
+        let center_y = self.y + (self.height - height) / 2;
+
+        match anchor {
+            PlacementAnchor::Center => (center_x, center_y),
+            PlacementAnchor::Top => (center_x, top),
+            PlacementAnchor::Bottom => (center_x, bottom),
+            PlacementAnchor::Left => (left, center_y),
+            PlacementAnchor::Right => (right, center_y),
+            PlacementAnchor::TopLeft => (left, top),
+            PlacementAnchor::TopRight => (right, top),
+            PlacementAnchor::BottomLeft => (left, bottom),
+            PlacementAnchor::BottomRight => (right, bottom),
+        }
+    }
+}
+
+/// レイヤーサーフェスに適用する配置
+pub struct Placement {
+    pub anchor: Anchor,
+    /// マージン（top, right, bottom, left）
+    pub margin: (i32, i32, i32, i32),
+}
+
+impl Placement {
+    /// 出力の中央（位置はコンポジタに任せる）
+    fn centered() -> Self {
+        Self {
+            anchor: Anchor::empty(),
+            margin: (0, 0, 0, 0),
+        }
+    }
+
+    /// 出力の端にレイヤーシェルのアンカーで配置（出力の位置・大きさが分からない場合）
+    ///
+    /// 中央揃えの軸はアンカーせずコンポジタに任せるため、その軸のオフセットは反映できない
+    /// （Centerでは両方、Top・Bottomではoffset_x、Left・Rightではoffset_y）。
+    /// 出力の矩形が分かる場合は[`Rect::anchored`]から位置を計算する
+    pub fn output_edge(anchor: PlacementAnchor, margin: i32, offset_x: i32, offset_y: i32) -> Self {
+        let (anchor, margin_top, margin_right, margin_bottom, margin_left) = match anchor {
+            PlacementAnchor::Center => (Anchor::empty(), 0, 0, 0, 0),
+            PlacementAnchor::Top => (Anchor::Top, margin + offset_y, 0, 0, 0),
+            PlacementAnchor::Bottom => (Anchor::Bottom, 0, 0, margin - offset_y, 0),
+            PlacementAnchor::Left => (Anchor::Left, 0, 0, 0, margin + offset_x),
+            PlacementAnchor::Right => (Anchor::Right, 0, margin - offset_x, 0, 0),
+            PlacementAnchor::TopLeft => (Anchor::Top | Anchor::Left, margin + offset_y, 0, 0, margin + offset_x),
+            PlacementAnchor::TopRight => (Anchor::Top | Anchor::Right, margin + offset_y, margin - offset_x, 0, 0),
+            PlacementAnchor::BottomLeft => (Anchor::Bottom | Anchor::Left, 0, 0, margin - offset_y, margin + offset_x),
+            PlacementAnchor::BottomRight => {
+                (Anchor::Bottom | Anchor::Right, 0, margin - offset_x, margin - offset_y, 0)
+            }
+        };
+
+        Self {
+            anchor,
+            margin: (margin_top, margin_right, margin_bottom, margin_left),
+        }
+    }
+
+    /// グローバル座標の左上位置から、モニタ相対の配置を作成（モニタ内にクランプ）
+    fn within(monitor: &Rect, x: i32, y: i32, width: i32, height: i32) -> Self {
+        // レイヤーサーフェスのマージンは出力相対
+        let left = x.min(monitor.x + monitor.width - width).max(monitor.x) - monitor.x;
+        let top = y.min(monitor.y + monitor.height - height).max(monitor.y) - monitor.y;
+
+        Self {
+            anchor: Anchor::Top | Anchor::Left,
+            margin: (top, 0, 0, left),
+        }
+    }
+}
+
+/// 配置の計算に使う情報源
+pub struct PlacementSources {
+    /// キャレット位置（キャレット配置の場合のみ）
+    pub caret: Option<CaretTracker>,
+    /// アクティブウィンドウ・モニタ情報
+    pub windows: WindowTracker,
+}
+
+/// 設定に従って、各出力に置くオーバーレイの配置を計算
+///
+/// `outputs`の要素がNoneの場合は出力をコンポジタに任せたサーフェス、
+/// Someの場合はその名前の出力に置くサーフェスの配置を返す
+pub fn compute(config: &Config, sources: &PlacementSources, outputs: &[Option<String>]) -> Vec<Placement> {
+    let placement = &config.placement;
+    let width = config.overlay.width as i32;
+    let height = config.overlay.height as i32;
+    let gap = placement.cursor_gap;
+    let monitors = sources.windows.monitors();
+
+    let offset = |(x, y): (i32, i32)| (x + placement.offset_x, y + placement.offset_y);
+    // 出力内のanchor位置（両方の軸を左上からの位置として指定するので、オフセットも両方に反映される）
+    let output_anchored = |monitor: &MonitorRect| {
+        let (x, y) = offset(monitor.rect.anchored(placement.anchor, placement.margin, width, height));
+        Placement::within(&monitor.rect, x, y, width, height)
+    };
+    // モニタ情報が取れない場合はレイヤーシェルのアンカーに任せる
+    let output_edge = || {
+        Placement::output_edge(placement.anchor, placement.margin, placement.offset_x, placement.offset_y)
+    };
+
+    // 出力ごとに基準が変わるOutputモード
+    if placement.mode == PlacementMode::Output {
+        return outputs
+            .iter()
+            .map(|output| {
+                let monitor = match output {
+                    Some(name) => monitors.iter().find(|monitor| &monitor.name == name),
+                    None => monitors.iter().find(|monitor| monitor.focused),
+                };
+                monitor.map_or_else(output_edge, output_anchored)
+            })
+            .collect();
+    }
+
+    let window_position = || {
+        sources
+            .windows
+            .active_window()
+            .map(|window| window.rect.anchored(placement.anchor, placement.margin, width, height))
+    };
+
+    let position = match placement.mode {
+        PlacementMode::ActiveWindow | PlacementMode::Output => window_position(),
+        PlacementMode::Absolute { x, y } => Some((x, y)),
+        PlacementMode::Caret => sources
+            .caret
+            .as_ref()
+            .and_then(CaretTracker::current)
+            .and_then(|caret| caret_position(caret, &sources.windows, gap))
+            .or_else(window_position),
+        PlacementMode::Pointer => pointer_position(&sources.windows, width, gap).or_else(window_position),
+    };
+
+    outputs
+        .iter()
+        .map(|output| {
+            // アクティブウィンドウが見つからない場合は画面中央
+            let Some((x, y)) = position.map(offset) else {
+                return Placement::centered();
+            };
+
+            match output {
+                // 位置を含むモニタ（無ければフォーカス中のモニタ）に置く
+                None => {
+                    let monitor = monitors
+                        .iter()
+                        .find(|monitor| monitor.rect.contains(x, y))
+                        .or_else(|| monitors.iter().find(|monitor| monitor.focused));
+                    match monitor {
+                        Some(monitor) => Placement::within(&monitor.rect, x, y, width, height),
+                        // モニタ情報が取れない場合はそのままマージンとして使う
+                        None => Placement {
+                            anchor: Anchor::Top | Anchor::Left,
+                            margin: (y, 0, 0, x),
+                        },
+                    }
+                }
+                // 指定出力が位置を含む場合だけその位置に、それ以外は出力内のanchor位置に置く
+                Some(name) => match monitors.iter().find(|monitor| &monitor.name == name) {
+                    Some(monitor) if monitor.rect.contains(x, y) => {
+                        Placement::within(&monitor.rect, x, y, width, height)
+                    }
+                    Some(monitor) => output_anchored(monitor),
+                    None => output_edge(),
+                },
+            }
+        })
+        .collect()
+}
+
+/// キャレットの直下に置く場合の左上座標
+fn caret_position(caret: CaretRect, windows: &WindowTracker, gap: i32) -> Option<(i32, i32)> {
+    // ウィンドウ相対の座標はアクティブウィンドウの位置を足してグローバル座標にする
+    let (origin_x, origin_y) = if caret.relative {
+        let window = windows.active_window()?;
+        (window.rect.x, window.rect.y)
+    } else {
+        (0, 0)
+    };
+
+    Some((origin_x + caret.x, origin_y + caret.y + caret.height + gap))
+}
+
+/// ポインタの直下に置く場合の左上座標
+fn pointer_position(windows: &WindowTracker, width: i32, gap: i32) -> Option<(i32, i32)> {
+    let (x, y) = windows.pointer()?;
+    Some((x - width / 2, y + gap))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_edge_applies_offsets_to_anchored_edges() {
+        let placement = Placement::output_edge(PlacementAnchor::TopLeft, 16, 5, -3);
+        assert_eq!(placement.anchor, Anchor::Top | Anchor::Left);
+        assert_eq!(placement.margin, (13, 0, 0, 21));
+
+        let placement = Placement::output_edge(PlacementAnchor::BottomRight, 16, 5, -3);
+        assert_eq!(placement.anchor, Anchor::Bottom | Anchor::Right);
+        assert_eq!(placement.margin, (0, 11, 19, 0));
+    }
+
+    #[test]
+    fn centered_anchor_on_known_output_keeps_offsets() {
+        let monitor = Rect {
+            x: 1920,
+            y: 0,
+            width: 1920,
+            height: 1080,
+        };
+        let (x, y) = monitor.anchored(PlacementAnchor::Center, 16, 200, 100);
+        let placement = Placement::within(&monitor, x + 30, y - 20, 200, 100);
+        assert_eq!(placement.anchor, Anchor::Top | Anchor::Left);
+        assert_eq!(placement.margin, (490 - 20, 0, 0, 860 + 30));
+    }
+}
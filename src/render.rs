@@ -0,0 +1,313 @@
+// テキストの描画（Cairo、CPUのみ）
+// オーバーレイ・バッジ・トレイアイコンのピクセルデータを作成する
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::config::{BadgeConfig, OverlayConfig, Theme};
+
+/// 描画スタイル（フォント、角丸ボックスの寸法、配色）
+pub struct RenderStyle {
+    font_family: String,
+    font_size: f64,
+    padding: f64,
+    corner_radius: f64,
+    theme: Theme,
+}
+
+impl RenderStyle {
+    /// フラッシュ表示用のスタイル（scaleは出力のバッファスケール）
+    pub fn overlay(overlay: &OverlayConfig, scale: i32) -> Self {
+        let scale = f64::from(scale);
+        Self {
+            font_family: overlay.font_family.clone(),
+            font_size: overlay.font_size * scale,
+            padding: overlay.padding * scale,
+            corner_radius: overlay.corner_radius * scale,
+            theme: overlay.theme,
+        }
+    }
+
+    /// トレイアイコン用のスタイル（背景は透明、sizeはアイコンの一辺）
+    pub fn tray(overlay: &OverlayConfig, size: i32) -> Self {
+        let size = f64::from(size);
+        Self {
+            font_family: overlay.font_family.clone(),
+            font_size: size * 0.6,
+            padding: 0.0,
+            corner_radius: size * 0.2,
+            theme: Theme {
+                background: (0.0, 0.0, 0.0, 0.0),
+                ..overlay.theme
+            },
+        }
+    }
+
+    /// 常時表示バッジ用のスタイル（scaleで拡大表示用の寸法にする）
+    pub fn badge(badge: &BadgeConfig, overlay: &OverlayConfig, scale: f64) -> Self {
+        Self {
+            font_family: overlay.font_family.clone(),
+            font_size: badge.font_size * scale,
+            padding: badge.padding * scale,
+            corner_radius: badge.corner_radius * scale,
+            theme: overlay.theme,
+        }
+    }
+}
+
+/// Cairoでテキストを描画してピクセルデータを返す
+pub fn render_text_to_pixels(
+    width: i32,
+    height: i32,
+    text: &str,
+    alpha: f64,
+    style: &RenderStyle,
+) -> Result<Vec<u8>> {
+    // Cairo ImageSurfaceを作成
+    let mut cairo_surface = cairo::ImageSurface::create(
+        cairo::Format::ARgb32,
+        width,
+        height,
+    )
+    .context("Cairo ImageSurfaceの作成に失敗")?;
+
+    // Cairo描画（背景 + 角丸ボックス + 文字、既定は黒背景 + 白いボックス + 黒文字）
+    {
+        let cairo_context = cairo::Context::new(&cairo_surface)
+            .context("Cairo Contextの作成に失敗")?;
+
+        let theme = &style.theme;
+
+        // 外側の背景を塗りつぶし
+        let (r, g, b, a) = theme.background;
+        cairo_context.set_source_rgba(r, g, b, a * alpha);
+        cairo_context.paint().context("背景描画に失敗")?;
+
+        // 内側の角丸ボックスを描画
+        let padding = style.padding;
+        let corner_radius = style.corner_radius;
+        let box_x = padding;
+        let box_y = padding;
+        let box_width = f64::from(width) - 2.0 * padding;
+        let box_height = f64::from(height) - 2.0 * padding;
+
+        // 角丸矩形のパスを作成
+        cairo_context.new_path();
+        cairo_context.arc(
+            box_x + box_width - corner_radius,
+            box_y + corner_radius,
+            corner_radius,
+            -std::f64::consts::PI / 2.0,
+            0.0,
+        );
+        cairo_context.arc(
+            box_x + box_width - corner_radius,
+            box_y + box_height - corner_radius,
+            corner_radius,
+            0.0,
+            std::f64::consts::PI / 2.0,
+        );
+        cairo_context.arc(
+            box_x + corner_radius,
+            box_y + box_height - corner_radius,
+            corner_radius,
+            std::f64::consts::PI / 2.0,
+            std::f64::consts::PI,
+        );
+        cairo_context.arc(
+            box_x + corner_radius,
+            box_y + corner_radius,
+            corner_radius,
+            std::f64::consts::PI,
+            3.0 * std::f64::consts::PI / 2.0,
+        );
+        cairo_context.close_path();
+
+        // ボックスの色で塗りつぶし
+        let (r, g, b, a) = theme.box_color;
+        cairo_context.set_source_rgba(r, g, b, a * alpha);
+        cairo_context.fill().context("角丸ボックス描画に失敗")?;
+
+        // テキストを描画（設定からフォントを取得）
+        cairo_context.select_font_face(
+            &style.font_family,
+            cairo::FontSlant::Normal,
+            cairo::FontWeight::Bold,
+        );
+        cairo_context.set_font_size(style.font_size);
+
+        // テキストのサイズを測定して中央配置
+        let extents = cairo_context.text_extents(text)
+            .context("テキストサイズ測定に失敗")?;
+
+        let text_x = (f64::from(width) - extents.width()) / 2.0 - extents.x_bearing();
+        let text_y = (f64::from(height) - extents.height()) / 2.0 - extents.y_bearing();
+
+        // テキストを描画
+        let (r, g, b, a) = theme.text;
+        cairo_context.set_source_rgba(r, g, b, a * alpha);
+        cairo_context.move_to(text_x, text_y);
+        cairo_context.show_text(text).context("テキスト描画に失敗")?;
+    }
+
+    // Cairoサーフェスのデータを取得
+    cairo_surface.flush();
+    let cairo_data = cairo_surface.data()
+        .context("Cairoデータの取得に失敗")?;
+
+    Ok(cairo_data.to_vec())
+}
+
+/// ピクセルデータ（ARGB32）をPNGファイルに書き出す
+pub fn write_png(width: i32, height: i32, pixels: &[u8], path: &Path) -> Result<()> {
+    let stride = cairo::Format::ARgb32
+        .stride_for_width(width as u32)
+        .context("ストライドの計算に失敗")?;
+    let surface = cairo::ImageSurface::create_for_data(pixels.to_vec(), cairo::Format::ARgb32, width, height, stride)
+        .context("Cairo ImageSurfaceの作成に失敗")?;
+    let mut file = std::fs::File::create(path).with_context(|| format!("{}を作成できません", path.display()))?;
+    surface
+        .write_to_png(&mut file)
+        .with_context(|| format!("{}への書き込みに失敗", path.display()))
+}
+
+/// オーバーレイ（バッファスケール1）と同じ描画をPNGファイルに書き出す（--render-png）
+pub fn render_png(overlay: &OverlayConfig, text: &str, path: &Path) -> Result<()> {
+    let (width, height) = (overlay.width as i32, overlay.height as i32);
+    let pixels = render_text_to_pixels(width, height, text, 1.0, &RenderStyle::overlay(overlay, 1))?;
+    write_png(width, height, &pixels, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::path::PathBuf;
+
+    /// 1画素の1成分あたりの許容差（Cairo・FreeTypeのバージョン差によるアンチエイリアスの揺れ）
+    const CHANNEL_TOLERANCE: u8 = 16;
+    /// 許容差を超えてよい画素の割合
+    const MAX_MISMATCHED_RATIO: f64 = 0.005;
+
+    fn fixture(path: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join(path)
+    }
+
+    fn overlay(width: u32, height: u32, theme: Theme) -> OverlayConfig {
+        OverlayConfig {
+            width,
+            height,
+            font_size: 48.0,
+            font_family: "DejaVu Sans".to_string(),
+            padding: 15.0,
+            corner_radius: 12.0,
+            theme,
+        }
+    }
+
+    fn render(width: i32, height: i32, text: &str, alpha: f64, style: &RenderStyle) -> Vec<u8> {
+        crate::fonts::use_test_fonts();
+        render_text_to_pixels(width, height, text, alpha, style).unwrap()
+    }
+
+    /// tests/golden/<name>.pngと比較（UPDATE_GOLDEN=1の場合は書き換える）
+    fn assert_matches_golden(name: &str, width: i32, height: i32, pixels: &[u8]) {
+        let path = fixture(&format!("golden/{}.png", name));
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            write_png(width, height, pixels, &path).unwrap();
+            return;
+        }
+
+        let mut file = std::fs::File::open(&path)
+            .unwrap_or_else(|e| panic!("{}を開けません（UPDATE_GOLDEN=1で作成）: {}", path.display(), e));
+        let mut golden = cairo::ImageSurface::create_from_png(&mut file).unwrap();
+        assert_eq!((golden.width(), golden.height()), (width, height), "{}: サイズが異なります", name);
+        // 不透明な画像はRGB24で読み込まれ、アルファの値は意味を持たない
+        let compare_alpha = golden.format() == cairo::Format::ARgb32;
+        let stride = golden.stride() as usize;
+        let golden_data = golden.data().unwrap();
+
+        let row_bytes = width as usize * 4;
+        let mut mismatched = 0;
+        for y in 0..height as usize {
+            let expected_row = &golden_data[y * stride..y * stride + row_bytes];
+            let actual_row = &pixels[y * row_bytes..(y + 1) * row_bytes];
+            for (expected, actual) in expected_row.chunks_exact(4).zip(actual_row.chunks_exact(4)) {
+                // ARGB32はリトルエンディアンでB, G, R, Aの順
+                let channels = if compare_alpha { 4 } else { 3 };
+                if (0..channels).any(|i| expected[i].abs_diff(actual[i]) > CHANNEL_TOLERANCE) {
+                    mismatched += 1;
+                }
+            }
+        }
+
+        let ratio = mismatched as f64 / (width * height) as f64;
+        if ratio > MAX_MISMATCHED_RATIO {
+            let actual_path = std::env::temp_dir().join(format!("{}.actual.png", name));
+            write_png(width, height, pixels, &actual_path).unwrap();
+            panic!(
+                "{}: {}画素（{:.2}%）が許容差を超えています（描画結果: {}）",
+                name,
+                mismatched,
+                ratio * 100.0,
+                actual_path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn overlay_default_theme() {
+        let style = RenderStyle::overlay(&overlay(200, 100, Config::built_in().overlay.theme), 1);
+        let pixels = render(200, 100, "en", 1.0, &style);
+        assert_matches_golden("overlay_default_theme", 200, 100, &pixels);
+    }
+
+    #[test]
+    fn overlay_custom_theme() {
+        let theme = Theme {
+            background: (0.0, 0.0, 0.0, 0.0),
+            box_color: (0.15, 0.2, 0.35, 1.0),
+            text: (1.0, 0.85, 0.3, 1.0),
+        };
+        let style = RenderStyle::overlay(&overlay(200, 100, theme), 1);
+        let pixels = render(200, 100, "JP", 1.0, &style);
+        assert_matches_golden("overlay_custom_theme", 200, 100, &pixels);
+    }
+
+    #[test]
+    fn overlay_scale_2() {
+        let style = RenderStyle::overlay(&overlay(200, 100, Config::built_in().overlay.theme), 2);
+        let pixels = render(400, 200, "en", 1.0, &style);
+        assert_matches_golden("overlay_scale_2", 400, 200, &pixels);
+    }
+
+    #[test]
+    fn overlay_fading() {
+        let style = RenderStyle::overlay(&overlay(200, 100, Config::built_in().overlay.theme), 1);
+        let pixels = render(200, 100, "en", 0.5, &style);
+        assert_matches_golden("overlay_fading", 200, 100, &pixels);
+    }
+
+    #[test]
+    fn tray_icon() {
+        let style = RenderStyle::tray(&overlay(200, 100, Config::built_in().overlay.theme), 32);
+        let pixels = render(32, 32, "A", 1.0, &style);
+        assert_matches_golden("tray_icon", 32, 32, &pixels);
+    }
+
+    #[test]
+    fn render_png_matches_overlay() {
+        let path = std::env::temp_dir().join("wayland_fcitx5_indicator-render_png.png");
+        crate::fonts::use_test_fonts();
+        render_png(&overlay(200, 100, Config::built_in().overlay.theme), "en", &path).unwrap();
+
+        // --render-pngの出力もオーバーレイのゴールデン画像と一致する
+        let mut file = std::fs::File::open(&path).unwrap();
+        let mut surface = cairo::ImageSurface::create_from_png(&mut file).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!((surface.width(), surface.height()), (200, 100));
+        let pixels = surface.data().unwrap().to_vec();
+        assert_matches_golden("overlay_default_theme", 200, 100, &pixels);
+    }
+}
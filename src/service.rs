@@ -0,0 +1,178 @@
+// デーモン操作用のDBusサービス（org.waylandfcitx5indicator.Daemon）
+// スクリプトやキーバインドから表示・再読み込み・一時停止を行う
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use crossbeam_channel::Sender;
+use dbus::blocking::stdintf::org_freedesktop_dbus::RequestNameReply;
+use dbus::blocking::Connection as DbusConnection;
+use dbus::channel::{MatchingReceiver, Sender as _};
+use dbus::message::MatchRule;
+use dbus::Message;
+use dbus_crossroads::{Crossroads, IfaceBuilder, MethodErr};
+
+use crate::config::{Config, SharedConfig};
+use crate::display::DisplayRequest;
+use crate::input_method::get_current_input_method;
+use crate::latency::{Stage, Trace};
+use crate::logging;
+
+pub const BUS_NAME: &str = "org.waylandfcitx5indicator.Daemon";
+pub const OBJECT_PATH: &str = "/org/waylandfcitx5indicator/Daemon";
+const INTERFACE: &str = "org.waylandfcitx5indicator.Daemon";
+
+/// DBusサービスと入力メソッドの監視で共有する状態
+#[derive(Clone)]
+pub struct DaemonState {
+    pub config: SharedConfig,
+    /// 最後に検出した入力メソッド
+    pub last_input_method: Arc<Mutex<String>>,
+    /// 一時停止中（入力メソッド変更時の自動表示のみ止める）
+    pub paused: Arc<AtomicBool>,
+    /// 表示スレッドへの表示リクエスト
+    pub tx: Sender<DisplayRequest>,
+}
+
+impl DaemonState {
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// 入力メソッドの変化を記録して表示（一時停止中は記録のみ）
+    pub fn update_input_method(&self, current: String, trace: Trace) {
+        let mut last = self.last_input_method.lock().unwrap();
+        if *last == current {
+            return;
+        }
+        info!("入力メソッド変更: {} -> {}", "Input method changed: {} -> {}", *last, current);
+        *last = current;
+
+        if !self.is_paused() {
+            self.show_input_method(&last, trace);
+        }
+    }
+
+    /// 設定ファイルを再読み込み（失敗した場合は以前の設定のまま）
+    pub fn reload(&self) -> Result<()> {
+        let config = Config::try_load()?;
+        logging::configure(&config.logging);
+        self.config.replace(config);
+        info!("設定ファイルを再読み込みしました", "Reloaded the config file");
+
+        // 新しい設定で現在の入力メソッドを表示し直す
+        let current = self.last_input_method.lock().unwrap().clone();
+        if !current.is_empty() {
+            self.show_input_method(&current, Trace::start());
+        }
+        Ok(())
+    }
+
+    /// 入力メソッドの表示名で表示をリクエスト
+    pub fn show_input_method(&self, input_method: &str, mut trace: Trace) {
+        let display_text = self.config.get().get_display_text(input_method);
+        trace.mark(Stage::Sent);
+        self.tx.send(DisplayRequest::Show(display_text, trace)).ok();
+    }
+}
+
+/// DBus名を取得（多重起動の防止）
+///
+/// 既に別のインスタンスが所有している場合はfalseを返す。`replace`の場合は名前を奪い、
+/// 奪われた側のインスタンスはNameLostを受け取って`terminate`を立てる（メインループが終了する）
+pub fn claim_name(conn: &DbusConnection, replace: bool, terminate: Arc<AtomicBool>) -> Result<bool> {
+    let reply = conn
+        .request_name(BUS_NAME, true, replace, true)
+        .context("DBus名の取得に失敗")?;
+    if !matches!(reply, RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner) {
+        return Ok(false);
+    }
+
+    // 後から--replaceで起動したインスタンスに名前を奪われたら終了
+    let rule = MatchRule::new_signal("org.freedesktop.DBus", "NameLost").with_sender("org.freedesktop.DBus");
+    conn.add_match(rule, move |(name,): (String,), _, _| {
+        if name == BUS_NAME {
+            info!("別のインスタンスに置き換えられたため終了します", "Replaced by another instance, exiting");
+            terminate.store(true, Ordering::Relaxed);
+        }
+        true
+    })
+    .context("マッチルールの追加に失敗")?;
+
+    Ok(true)
+}
+
+/// 起動中のインスタンスに設定の再読み込みを依頼
+pub fn request_reload(conn: &DbusConnection) -> Result<()> {
+    conn.with_proxy(BUS_NAME, OBJECT_PATH, Duration::from_secs(2))
+        .method_call::<(), _, _, _>(INTERFACE, "Reload", ())
+        .context("起動中のインスタンスの再読み込みに失敗")
+}
+
+/// デーモンのオブジェクトを登録（公開はserveで行う）
+pub fn register(cr: &mut Crossroads, state: DaemonState) {
+    let iface = cr.register(INTERFACE, |b: &mut IfaceBuilder<DaemonState>| {
+        // 任意のテキストを表示
+        b.method("Show", ("text",), (), |_, state, (text,): (String,)| {
+            let mut trace = Trace::start();
+            trace.mark(Stage::Sent);
+            state.tx.send(DisplayRequest::Show(text, trace)).ok();
+            Ok(())
+        });
+        // 現在の入力メソッドを表示
+        b.method("ShowCurrent", (), (), |_, state, ()| {
+            let mut trace = Trace::start();
+            let current = get_current_input_method()
+                .map_err(|e| MethodErr::failed(&format!("入力メソッドを取得できません: {}", e)))?;
+            trace.mark(Stage::Queried);
+            state.show_input_method(&current, trace);
+            Ok(())
+        });
+        // 設定ファイルを再読み込み
+        b.method("Reload", (), (), |_, state, ()| {
+            state.reload().map_err(|e| MethodErr::failed(&format!("{:#}", e)))
+        });
+        b.method("Pause", (), (), |_, state, ()| {
+            state.paused.store(true, Ordering::Relaxed);
+            state.tx.send(DisplayRequest::PauseChanged).ok();
+            info!("一時停止しました", "Paused");
+            Ok(())
+        });
+        b.method("Resume", (), (), |_, state, ()| {
+            state.paused.store(false, Ordering::Relaxed);
+            state.tx.send(DisplayRequest::PauseChanged).ok();
+            info!("再開しました", "Resumed");
+            Ok(())
+        });
+        // 現在の状態（入力メソッド、表示名、一時停止中か）
+        b.method("GetState", (), ("im", "label", "paused"), |_, state, ()| {
+            let current = state.last_input_method.lock().unwrap().clone();
+            let label = state.config.get().get_display_text(&current);
+            Ok((current, label, state.is_paused()))
+        });
+        b.signal::<(String, String), _>("Changed", ("im", "label"));
+    });
+    cr.insert(OBJECT_PATH, &[iface], state);
+}
+
+/// 登録したオブジェクトを公開（メソッド呼び出しはconnのprocess()で処理される）
+///
+/// 事前にclaim_nameで名前を取得しておくこと
+pub fn serve(conn: &DbusConnection, mut cr: Crossroads) {
+    conn.start_receive(
+        MatchRule::new_method_call(),
+        Box::new(move |msg, conn| {
+            cr.handle_message(msg, conn).ok();
+            true
+        }),
+    );
+}
+
+/// 入力メソッドの変更を通知（Changedシグナル）
+pub fn emit_changed(conn: &DbusConnection, input_method: &str, label: &str) {
+    let signal = Message::signal(&OBJECT_PATH.into(), &INTERFACE.into(), &"Changed".into())
+        .append2(input_method, label);
+    conn.send(signal).ok();
+}
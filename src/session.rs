@@ -0,0 +1,116 @@
+// セッションのロック状態（logindのLockedHint）
+// ext-session-lock-v1はロック中かどうかを他のクライアントに公開しないため、
+// ロッカーが設定するlogindのLockedHintで判定する
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use crossbeam_channel::{Receiver, Sender};
+use dbus::arg::prop_cast;
+use dbus::blocking::stdintf::org_freedesktop_dbus::{Properties, PropertiesPropertiesChanged};
+use dbus::blocking::Connection as DbusConnection;
+use dbus::message::SignalArgs;
+use dbus::Path;
+
+const LOGIN1_DEST: &str = "org.freedesktop.login1";
+const LOGIN1_PATH: &str = "/org/freedesktop/login1";
+const MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+const SESSION_PATH: &str = "/org/freedesktop/login1/session/auto";
+const SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+const CALL_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// セッションのロック状態（LockedHintをキャッシュし、PropertiesChangedで更新する）
+///
+/// 表示のたびにlogindへ問い合わせないよう、監視スレッドがシグナルでキャッシュを更新する。
+/// 監視スレッドは、これが破棄されると終了する
+pub struct SessionLock {
+    locked: Arc<AtomicBool>,
+    /// ロック状態が変わるたびに新しい値を受け取る
+    changes: Receiver<bool>,
+    /// 監視スレッドはこれの弱参照だけを持ち、所有者がいなくなったことを知る
+    _owner: Arc<()>,
+}
+
+impl SessionLock {
+    /// システムバスに接続して監視を開始（失敗した場合は常に「ロックされていない」とみなす）
+    pub fn connect() -> Self {
+        let locked = Arc::new(AtomicBool::new(false));
+        let owner = Arc::new(());
+        let (tx, changes) = crossbeam_channel::unbounded();
+        let changes = match watch(Arc::clone(&locked), tx, Arc::downgrade(&owner)) {
+            Ok(()) => changes,
+            Err(e) => {
+                warn!(
+                    "画面ロックの状態を取得できません（画面ロックを検出しません）: {:#}",
+                    "Cannot get the screen lock state (screen lock will not be detected): {:#}",
+                    e
+                );
+                crossbeam_channel::never()
+            }
+        };
+        Self {
+            locked,
+            changes,
+            _owner: owner,
+        }
+    }
+
+    /// セッションがロックされているかどうか（キャッシュ）
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    /// ロック状態の変化（常時表示バッジの表示・非表示の切り替え用）
+    pub fn changes(&self) -> &Receiver<bool> {
+        &self.changes
+    }
+}
+
+/// LockedHintの現在の値を取得し、変化を監視するスレッドを起動
+fn watch(locked: Arc<AtomicBool>, tx: Sender<bool>, owner: Weak<()>) -> Result<()> {
+    let conn = DbusConnection::new_system().context("システムバスへの接続に失敗")?;
+
+    // PropertiesChangedは実際のセッションのパスから送られるため、autoを解決する
+    let id: String = conn
+        .with_proxy(LOGIN1_DEST, SESSION_PATH, CALL_TIMEOUT)
+        .get(SESSION_INTERFACE, "Id")
+        .context("セッションIDを取得できません")?;
+    let (path,): (Path<'static>,) = conn
+        .with_proxy(LOGIN1_DEST, LOGIN1_PATH, CALL_TIMEOUT)
+        .method_call(MANAGER_INTERFACE, "GetSession", (id,))
+        .context("セッションのパスを取得できません")?;
+
+    let rule = PropertiesPropertiesChanged::match_rule(Some(&LOGIN1_DEST.into()), Some(&path)).static_clone();
+    let state = Arc::clone(&locked);
+    conn.add_match(rule, move |changed: PropertiesPropertiesChanged, _, _| {
+        if changed.interface_name == SESSION_INTERFACE {
+            if let Some(&value) = prop_cast::<bool>(&changed.changed_properties, "LockedHint") {
+                if state.swap(value, Ordering::Relaxed) != value {
+                    tx.send(value).ok();
+                }
+            }
+        }
+        true
+    })
+    .context("マッチルールの追加に失敗")?;
+
+    // マッチルールを追加してから現在の値を取得する（間の変化を取りこぼさない）
+    let current: bool = conn
+        .with_proxy(LOGIN1_DEST, &path, CALL_TIMEOUT)
+        .get(SESSION_INTERFACE, "LockedHint")
+        .context("LockedHintを取得できません")?;
+    locked.store(current, Ordering::Relaxed);
+
+    std::thread::spawn(move || {
+        while owner.strong_count() > 0 {
+            if let Err(e) = conn.process(Duration::from_millis(1000)) {
+                error!("画面ロックの監視エラー: {}", "Screen lock watch error: {}", e);
+                break;
+            }
+        }
+    });
+
+    Ok(())
+}
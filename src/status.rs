@@ -0,0 +1,71 @@
+// ステータスバー（Waybarのcustomモジュール）向けの出力
+// 入力メソッドが変わるたびにJSONを1行出力する（return-type: json）
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use dbus::blocking::Connection as DbusConnection;
+use dbus::message::MatchRule;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::input_method::get_current_input_method;
+
+/// Waybarのcustomモジュールが読むJSON
+#[derive(Serialize)]
+struct WaybarStatus {
+    text: String,
+    /// ツールチップ（logging.languageの言語）
+    tooltip: String,
+    /// CSSクラス（入力メソッド名、fcitx5に接続できない場合は"unavailable"）
+    class: String,
+    /// format-iconsのキー（入力メソッド名）
+    alt: String,
+}
+
+impl WaybarStatus {
+    fn new(config: &Config, input_method: Option<&str>) -> Self {
+        match input_method {
+            Some(input_method) => {
+                let label = config.get_display_text(input_method);
+                Self {
+                    tooltip: localized!("入力メソッド: {} ({})", "Input method: {} ({})", input_method, label),
+                    text: label,
+                    class: input_method.to_string(),
+                    alt: input_method.to_string(),
+                }
+            }
+            None => Self {
+                text: String::new(),
+                tooltip: localized!("fcitx5に接続できません", "Cannot connect to fcitx5"),
+                class: "unavailable".to_string(),
+                alt: "unavailable".to_string(),
+            },
+        }
+    }
+}
+
+/// 入力メソッドを監視してJSONを出力し続ける（オーバーレイは表示しない）
+pub fn run_waybar(config: &Config) -> Result<()> {
+    let dbus_conn = DbusConnection::new_session()
+        .context("DBusセッションバスへの接続に失敗")?;
+
+    // シグナルを受けたらprocess()から戻るので、直後の確認ですぐに反映される
+    let rule = MatchRule::new_signal("org.freedesktop.DBus.Properties", "PropertiesChanged")
+        .with_sender("org.fcitx.Fcitx5");
+    dbus_conn.add_match(rule, |_: (), _, _| true)
+        .context("マッチルールの追加に失敗")?;
+
+    // 最初の1回は必ず出力する
+    let mut last: Option<Option<String>> = None;
+    loop {
+        let current = get_current_input_method().ok();
+        if last.as_ref() != Some(&current) {
+            let status = WaybarStatus::new(config, current.as_deref());
+            println!("{}", serde_json::to_string(&status)?);
+            last = Some(current);
+        }
+
+        dbus_conn.process(Duration::from_millis(500))?;
+    }
+}
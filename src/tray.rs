@@ -0,0 +1,388 @@
+// トレイアイコン（StatusNotifierItem）
+// 現在の入力メソッドの表示テキストをアイコンに描画し、メニューから入力メソッドを切り替える
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use dbus::arg::{PropMap, RefArg, Variant};
+use dbus::blocking::Connection as DbusConnection;
+use dbus::channel::{MatchingReceiver, Sender as _};
+use dbus::message::MatchRule;
+use dbus::Message;
+use dbus_crossroads::{Context as MethodContext, Crossroads, IfaceBuilder, MethodErr};
+
+use crate::input_method::get_current_input_method;
+use crate::latency::{Stage, Trace};
+use crate::service::{self, DaemonState};
+use crate::render::{render_text_to_pixels, RenderStyle};
+
+const ITEM_PATH: &str = "/StatusNotifierItem";
+const ITEM_INTERFACE: &str = "org.kde.StatusNotifierItem";
+const MENU_PATH: &str = "/MenuBar";
+const MENU_INTERFACE: &str = "com.canonical.dbusmenu";
+const WATCHER_NAME: &str = "org.kde.StatusNotifierWatcher";
+const WATCHER_PATH: &str = "/StatusNotifierWatcher";
+
+/// アイコンの一辺（ピクセル、表示サイズへの縮小はトレイ側が行う）
+const ICON_SIZE: i32 = 32;
+
+/// StatusNotifierItemのアイコン（幅、高さ、ネットワークバイトオーダーのARGB32）
+type IconPixmap = Vec<(i32, i32, Vec<u8>)>;
+
+/// dbusmenuのレイアウト（ID、プロパティ、子要素）
+type MenuLayout = (i32, PropMap, Vec<Variant<Box<dyn RefArg>>>);
+
+/// dbusmenuのイベント（ID、イベントの種類、データ、タイムスタンプ）
+type MenuEvent = (i32, String, Variant<Box<dyn RefArg>>, u32);
+
+/// メニューの状態
+#[derive(Default)]
+struct MenuState {
+    /// 現在のグループの入力メソッド（メニュー項目のIDはインデックス+1）
+    input_methods: Vec<String>,
+    /// レイアウトのリビジョン（項目や選択状態が変わるたびに増やす）
+    revision: u32,
+}
+
+/// トレイアイコン（メソッド呼び出しの処理と変更通知で共有）
+#[derive(Clone)]
+pub struct Tray {
+    state: DaemonState,
+    menu: Arc<Mutex<MenuState>>,
+}
+
+impl Tray {
+    /// 現在の入力メソッドと表示テキスト
+    fn current(&self) -> (String, String) {
+        let current = self.state.last_input_method.lock().unwrap().clone();
+        let label = self.state.config.get().get_display_text(&current);
+        (current, label)
+    }
+
+    /// 表示テキストを描画したアイコン
+    fn icon_pixmap(&self) -> Result<IconPixmap> {
+        let (_, label) = self.current();
+        let config = self.state.config.get();
+        let style = RenderStyle::tray(&config.overlay, ICON_SIZE);
+        let pixels = render_text_to_pixels(ICON_SIZE, ICON_SIZE, &label, 1.0, &style)?;
+
+        // CairoのARGB32はネイティブエンディアンでアルファ乗算済みなので、
+        // 乗算を戻してからネットワークバイトオーダーに並べ替える
+        let pixels = pixels
+            .chunks_exact(4)
+            .flat_map(|pixel| {
+                unpremultiply(u32::from_ne_bytes([pixel[0], pixel[1], pixel[2], pixel[3]])).to_be_bytes()
+            })
+            .collect();
+
+        Ok(vec![(ICON_SIZE, ICON_SIZE, pixels)])
+    }
+
+    /// ツールチップ（アイコン名、アイコン、タイトル、説明）
+    fn tool_tip(&self) -> (String, IconPixmap, String, String) {
+        let (current, label) = self.current();
+        (String::new(), Vec::new(), format!("入力メソッド: {}", label), current)
+    }
+
+    /// fcitx5から現在のグループの入力メソッドを取得し直す（変化があればリビジョンを増やす）
+    ///
+    /// メソッド呼び出しの処理中は応答を待てないため、デーモンの接続で登録時と入力メソッドの変更時に行う
+    fn refresh_menu(&self, conn: &DbusConnection) -> bool {
+        let input_methods = match current_group_input_methods(conn) {
+            Ok(input_methods) => input_methods,
+            Err(e) => {
+                debug!("トレイのメニューを更新できません: {:#}", "Cannot refresh the tray menu: {:#}", e);
+                return false;
+            }
+        };
+
+        let mut menu = self.menu.lock().unwrap();
+        if menu.input_methods == input_methods {
+            return false;
+        }
+        menu.input_methods = input_methods;
+        menu.revision += 1;
+        true
+    }
+
+    /// メニュー項目のプロパティ（ID 0はルート）
+    fn item_properties(&self, id: i32) -> Option<PropMap> {
+        let mut properties = PropMap::new();
+        if id == 0 {
+            properties.insert("children-display".into(), Variant(Box::new("submenu".to_string())));
+            return Some(properties);
+        }
+
+        let menu = self.menu.lock().unwrap();
+        let input_method = menu.input_methods.get(usize::try_from(id - 1).ok()?)?;
+        let (current, _) = self.current();
+        let label = self.state.config.get().get_display_text(input_method);
+
+        properties.insert("label".into(), Variant(Box::new(format!("{} ({})", label, input_method))));
+        properties.insert("toggle-type".into(), Variant(Box::new("radio".to_string())));
+        properties.insert(
+            "toggle-state".into(),
+            Variant(Box::new(i32::from(*input_method == current))),
+        );
+        Some(properties)
+    }
+
+    /// dbusmenuのレイアウト（ルートの下に入力メソッドを並べる）
+    fn layout(&self, parent_id: i32) -> Result<(u32, MenuLayout), MethodErr> {
+        let properties = self
+            .item_properties(parent_id)
+            .ok_or_else(|| MethodErr::invalid_arg(&parent_id))?;
+        let menu = self.menu.lock().unwrap();
+        let children = if parent_id == 0 { menu.input_methods.len() } else { 0 };
+        let revision = menu.revision;
+        drop(menu);
+
+        let children = (1..=children as i32)
+            .filter_map(|id| {
+                let child: MenuLayout = (id, self.item_properties(id)?, Vec::new());
+                Some(Variant(Box::new(child) as Box<dyn RefArg>))
+            })
+            .collect();
+
+        Ok((revision, (parent_id, properties, children)))
+    }
+
+    /// メニュー項目のクリック（入力メソッドを切り替える）
+    ///
+    /// 切り替えの呼び出しは、メソッド呼び出しを受けたデーモンの接続から応答を待たずに送る
+    fn clicked(&self, ctx: &mut MethodContext, id: i32) -> Result<(), MethodErr> {
+        let input_method = {
+            let menu = self.menu.lock().unwrap();
+            usize::try_from(id - 1)
+                .ok()
+                .and_then(|index| menu.input_methods.get(index).cloned())
+                .ok_or_else(|| MethodErr::invalid_arg(&id))?
+        };
+
+        ctx.push_msg(set_current_input_method_message(&input_method));
+        info!("トレイから入力メソッドを切り替え: {}", "Switched input method from the tray: {}", input_method);
+        Ok(())
+    }
+
+    /// 入力メソッドの変更をトレイに通知（アイコン・タイトル・ツールチップ・メニューの選択状態）
+    pub fn notify_changed(&self, conn: &DbusConnection) {
+        // グループが切り替わっている場合に備えてメニューの項目も取得し直す
+        self.refresh_menu(conn);
+        for member in ["NewIcon", "NewTitle", "NewToolTip"] {
+            let signal = Message::signal(&ITEM_PATH.into(), &ITEM_INTERFACE.into(), &member.into());
+            conn.send(signal).ok();
+        }
+
+        let revision = {
+            let mut menu = self.menu.lock().unwrap();
+            menu.revision += 1;
+            menu.revision
+        };
+        let signal = Message::signal(&MENU_PATH.into(), &MENU_INTERFACE.into(), &"LayoutUpdated".into())
+            .append2(revision, 0i32);
+        conn.send(signal).ok();
+    }
+}
+
+/// トレイアイコンのオブジェクトを登録し、StatusNotifierWatcherに登録する
+///
+/// 登録したオブジェクトはservice::serveで公開される
+pub fn register(cr: &mut Crossroads, conn: &DbusConnection, state: DaemonState) -> Result<Tray> {
+    let tray = Tray {
+        state,
+        menu: Arc::default(),
+    };
+
+    let item = cr.register(ITEM_INTERFACE, |b: &mut IfaceBuilder<Tray>| {
+        b.property("Category").get(|_, _| Ok("SystemServices".to_string()));
+        b.property("Id").get(|_, _| Ok(env!("CARGO_PKG_NAME").to_string()));
+        b.property("Title").get(|_, tray| Ok(tray.current().1));
+        b.property("Status").get(|_, _| Ok("Active".to_string()));
+        b.property("WindowId").get(|_, _| Ok(0i32));
+        b.property("IconName").get(|_, _| Ok(String::new()));
+        b.property("IconPixmap").get(|_, tray| {
+            tray.icon_pixmap().map_err(|e| MethodErr::failed(&format!("{:#}", e)))
+        });
+        b.property("ToolTip").get(|_, tray| Ok(tray.tool_tip()));
+        b.property("ItemIsMenu").get(|_, _| Ok(false));
+        b.property("Menu").get(|_, _| Ok(dbus::Path::from(MENU_PATH)));
+
+        // 左クリックで現在の入力メソッドを表示
+        b.method("Activate", ("x", "y"), (), |_, tray, (_, _): (i32, i32)| {
+            let mut trace = Trace::start();
+            let current = get_current_input_method()
+                .map_err(|e| MethodErr::failed(&format!("入力メソッドを取得できません: {}", e)))?;
+            trace.mark(Stage::Queried);
+            tray.state.show_input_method(&current, trace);
+            Ok(())
+        });
+        b.method("SecondaryActivate", ("x", "y"), (), |_, _, (_, _): (i32, i32)| Ok(()));
+        b.method("ContextMenu", ("x", "y"), (), |_, _, (_, _): (i32, i32)| Ok(()));
+        b.method("Scroll", ("delta", "orientation"), (), |_, _, (_, _): (i32, String)| Ok(()));
+
+        b.signal::<(), _>("NewTitle", ());
+        b.signal::<(), _>("NewIcon", ());
+        b.signal::<(), _>("NewToolTip", ());
+        b.signal::<(String,), _>("NewStatus", ("status",));
+    });
+
+    let menu = cr.register(MENU_INTERFACE, |b: &mut IfaceBuilder<Tray>| {
+        b.property("Version").get(|_, _| Ok(3u32));
+        b.property("TextDirection").get(|_, _| Ok("ltr".to_string()));
+        b.property("Status").get(|_, _| Ok("normal".to_string()));
+        b.property("IconThemePath").get(|_, _| Ok(Vec::<String>::new()));
+
+        b.method(
+            "GetLayout",
+            ("parentId", "recursionDepth", "propertyNames"),
+            ("revision", "layout"),
+            |_, tray, (parent_id, _, _): (i32, i32, Vec<String>)| tray.layout(parent_id),
+        );
+        b.method(
+            "GetGroupProperties",
+            ("ids", "propertyNames"),
+            ("properties",),
+            |_, tray, (ids, _): (Vec<i32>, Vec<String>)| {
+                let properties: Vec<(i32, PropMap)> = ids
+                    .into_iter()
+                    .filter_map(|id| Some((id, tray.item_properties(id)?)))
+                    .collect();
+                Ok((properties,))
+            },
+        );
+        b.method(
+            "GetProperty",
+            ("id", "name"),
+            ("value",),
+            |_, tray, (id, name): (i32, String)| {
+                tray.item_properties(id)
+                    .and_then(|mut properties| properties.remove(&name))
+                    .map(|value| (value,))
+                    .ok_or_else(|| MethodErr::invalid_arg(&name))
+            },
+        );
+        b.method(
+            "Event",
+            ("id", "eventId", "data", "timestamp"),
+            (),
+            |ctx, tray, (id, event_id, _, _): MenuEvent| {
+                if event_id == "clicked" {
+                    tray.clicked(ctx, id)?;
+                }
+                Ok(())
+            },
+        );
+        b.method(
+            "EventGroup",
+            ("events",),
+            ("idErrors",),
+            |ctx, tray, (events,): (Vec<MenuEvent>,)| {
+                let errors: Vec<i32> = events
+                    .into_iter()
+                    .filter(|(id, event_id, _, _)| event_id == "clicked" && tray.clicked(ctx, *id).is_err())
+                    .map(|(id, _, _, _)| id)
+                    .collect();
+                Ok((errors,))
+            },
+        );
+        // メニューは入力メソッドの変更時に更新済みなので、表示前の更新は不要
+        b.method("AboutToShow", ("id",), ("needUpdate",), |_, _, (_,): (i32,)| Ok((false,)));
+        b.method(
+            "AboutToShowGroup",
+            ("ids",),
+            ("updatesNeeded", "idErrors"),
+            |_, _, (_,): (Vec<i32>,)| Ok((Vec::<i32>::new(), Vec::<i32>::new())),
+        );
+
+        b.signal::<(u32, i32), _>("LayoutUpdated", ("revision", "parent"));
+        b.signal::<(Vec<(i32, PropMap)>, Vec<(i32, Vec<String>)>), _>(
+            "ItemsPropertiesUpdated",
+            ("updatedProps", "removedProps"),
+        );
+    });
+
+    cr.insert(ITEM_PATH, &[item], tray.clone());
+    cr.insert(MENU_PATH, &[menu], tray.clone());
+    tray.refresh_menu(conn);
+
+    // トレイ（StatusNotifierWatcher）が後から起動・再起動した場合も登録し直す
+    // （他の名前のNameOwnerChangedで起こされないよう、バス側でarg0を絞り込む）
+    let bus_rule = format!(
+        "type='signal',sender='org.freedesktop.DBus',interface='org.freedesktop.DBus',\
+         member='NameOwnerChanged',arg0='{}'",
+        WATCHER_NAME
+    );
+    conn.with_proxy("org.freedesktop.DBus", "/org/freedesktop/DBus", Duration::from_secs(2))
+        .method_call::<(), _, _, _>("org.freedesktop.DBus", "AddMatch", (bus_rule,))
+        .context("マッチルールの追加に失敗")?;
+    conn.start_receive(
+        MatchRule::new_signal("org.freedesktop.DBus", "NameOwnerChanged").with_sender("org.freedesktop.DBus"),
+        Box::new(|msg, conn| {
+            if let Ok((name, _, new_owner)) = msg.read3::<String, String, String>() {
+                if name == WATCHER_NAME && !new_owner.is_empty() {
+                    conn.send(register_message()).ok();
+                }
+            }
+            true
+        }),
+    );
+
+    let registered = conn
+        .with_proxy(WATCHER_NAME, WATCHER_PATH, Duration::from_secs(2))
+        .method_call::<(), _, _, _>(WATCHER_NAME, "RegisterStatusNotifierItem", (service::BUS_NAME,));
+    if let Err(e) = registered {
+        warn!(
+            "StatusNotifierWatcherに登録できません（トレイの起動後に登録します）: {}",
+            "Cannot register with StatusNotifierWatcher (will register once the tray starts): {}",
+            e
+        );
+    }
+
+    Ok(tray)
+}
+
+/// StatusNotifierWatcherへの登録メッセージ（デーモンのDBus名で登録）
+fn register_message() -> Message {
+    Message::new_method_call(WATCHER_NAME, WATCHER_PATH, WATCHER_NAME, "RegisterStatusNotifierItem")
+        .expect("StatusNotifierWatcherの呼び出しの作成に失敗")
+        .append1(service::BUS_NAME)
+}
+
+/// fcitx5の現在のグループの入力メソッド
+fn current_group_input_methods(conn: &DbusConnection) -> Result<Vec<String>> {
+    let proxy = conn.with_proxy("org.fcitx.Fcitx5", "/controller", Duration::from_millis(5000));
+
+    let (group,): (String,) = proxy
+        .method_call("org.fcitx.Fcitx.Controller1", "CurrentInputMethodGroup", ())
+        .context("fcitx5から入力メソッドグループの取得に失敗")?;
+    let (_, items): (String, Vec<(String, String)>) = proxy
+        .method_call("org.fcitx.Fcitx.Controller1", "InputMethodGroupInfo", (group,))
+        .context("fcitx5から入力メソッドグループの取得に失敗")?;
+
+    Ok(items.into_iter().map(|(input_method, _)| input_method).collect())
+}
+
+/// fcitx5の入力メソッドを切り替える呼び出し（応答は待たない）
+fn set_current_input_method_message(input_method: &str) -> Message {
+    let mut message = Message::new_method_call(
+        "org.fcitx.Fcitx5",
+        "/controller",
+        "org.fcitx.Fcitx.Controller1",
+        "SetCurrentIM",
+    )
+    .expect("fcitx5の呼び出しの作成に失敗")
+    .append1(input_method);
+    message.set_no_reply(true);
+    message
+}
+
+/// アルファ乗算済みのARGBを、StatusNotifierItemのIconPixmapが前提とするストレートアルファに戻す
+fn unpremultiply(argb: u32) -> u32 {
+    let alpha = argb >> 24;
+    if alpha == 0 || alpha == 255 {
+        return argb;
+    }
+    let channel = |shift: u32| (((argb >> shift) & 0xff) * 255 + alpha / 2) / alpha;
+    (alpha << 24) | (channel(16) << 16) | (channel(8) << 8) | channel(0)
+}
@@ -0,0 +1,361 @@
+// 設定値の検証
+// 解析はできても描画・表示で問題になる値（0の幅、ボックスに収まらない余白、見つからないフォントなど）を
+// フィールドのパス付きでまとめて報告する
+
+use std::fmt;
+
+use crate::config::{AnimationConfig, BadgeConfig, Config, OverlayConfig, Rule, Theme};
+use crate::fonts;
+
+/// 問題の重大度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// 設定を使えない（読み込みを失敗させる）
+    Error,
+    /// 設定は使えるが、意図どおりに表示されない可能性がある
+    Warning,
+}
+
+/// 設定の問題
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub severity: Severity,
+    /// フィールドのパス（例: `overlay.width`、`rules[0].theme.text`）
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// 検証で見つかったエラー（見つかったものをすべて保持）
+#[derive(Debug)]
+pub struct InvalidConfig(pub Vec<Problem>);
+
+impl fmt::Display for InvalidConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // ログで1行に収まるように区切る
+        write!(f, "設定値のエラー（{}件）: ", self.0.len())?;
+        for (index, problem) in self.0.iter().enumerate() {
+            if index > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InvalidConfig {}
+
+impl Config {
+    /// 設定値を検証し、見つかった問題をすべて返す（フォントがfontconfigで見つかるかも確認する）
+    pub fn validate(&self) -> Vec<Problem> {
+        let mut checker = Checker::default();
+        checker.overlay(&self.overlay);
+        checker.animation(&self.animation);
+        checker.badge(&self.indicator.badge);
+        for (index, rule) in self.rules.iter().enumerate() {
+            checker.rule(&format!("rules[{}]", index), rule, &self.overlay);
+        }
+        checker.font_family("overlay.font_family", &self.overlay.font_family);
+        checker.problems
+    }
+
+    /// 設定値を検証し、警告はログに出力してエラーがあれば返す
+    pub fn check(&self) -> Result<(), InvalidConfig> {
+        let (errors, warnings): (Vec<Problem>, Vec<Problem>) = self
+            .validate()
+            .into_iter()
+            .partition(|problem| problem.severity == Severity::Error);
+        for warning in warnings {
+            warn!("設定値の警告: {}", "Config warning: {}", warning);
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(InvalidConfig(errors))
+        }
+    }
+}
+
+/// 見つかった問題を集める
+#[derive(Default)]
+struct Checker {
+    problems: Vec<Problem>,
+}
+
+impl Checker {
+    fn report(&mut self, severity: Severity, path: &str, message: String) {
+        self.problems.push(Problem {
+            severity,
+            path: path.to_string(),
+            message,
+        });
+    }
+
+    fn error(&mut self, path: &str, message: String) {
+        self.report(Severity::Error, path, message);
+    }
+
+    fn warning(&mut self, path: &str, message: String) {
+        self.report(Severity::Warning, path, message);
+    }
+
+    fn overlay(&mut self, overlay: &OverlayConfig) {
+        self.size("overlay.width", overlay.width);
+        self.size("overlay.height", overlay.height);
+        self.positive("overlay.font_size", overlay.font_size);
+        self.non_negative("overlay.padding", overlay.padding);
+        self.non_negative("overlay.corner_radius", overlay.corner_radius);
+        self.theme("overlay.theme", &overlay.theme);
+        self.rounded_box(
+            ("overlay.padding", "overlay.padding", "overlay.corner_radius"),
+            (overlay.width, overlay.height),
+            overlay.padding,
+            overlay.corner_radius,
+        );
+    }
+
+    fn animation(&mut self, animation: &AnimationConfig) {
+        if animation.fade_frames == 0 && animation.fade_duration_ms > 0 {
+            self.error(
+                "animation.fade_frames",
+                format!(
+                    "fade_duration_ms（{}）が0でない場合は1以上にしてください（フェードしない場合はfade_duration_msも0にしてください）",
+                    animation.fade_duration_ms
+                ),
+            );
+        }
+    }
+
+    fn badge(&mut self, badge: &BadgeConfig) {
+        self.size("indicator.badge.width", badge.width);
+        self.size("indicator.badge.height", badge.height);
+        self.positive("indicator.badge.font_size", badge.font_size);
+        self.non_negative("indicator.badge.padding", badge.padding);
+        self.non_negative("indicator.badge.corner_radius", badge.corner_radius);
+        if !(badge.enlarge_scale.is_finite() && badge.enlarge_scale >= 1.0) {
+            self.error(
+                "indicator.badge.enlarge_scale",
+                format!("1.0以上にしてください（{}、1.0で拡大しない）", badge.enlarge_scale),
+            );
+        }
+        self.rounded_box(
+            ("indicator.badge.padding", "indicator.badge.padding", "indicator.badge.corner_radius"),
+            (badge.width, badge.height),
+            badge.padding,
+            badge.corner_radius,
+        );
+    }
+
+    /// ルールの上書き（余白と角の半径はoverlayのものを使う）
+    fn rule(&mut self, path: &str, rule: &Rule, overlay: &OverlayConfig) {
+        if let Some(width) = rule.width {
+            self.size(&format!("{}.width", path), width);
+        }
+        if let Some(height) = rule.height {
+            self.size(&format!("{}.height", path), height);
+        }
+        if let Some(font_size) = rule.font_size {
+            self.positive(&format!("{}.font_size", path), font_size);
+        }
+        if let Some(theme) = &rule.theme {
+            self.theme(&format!("{}.theme", path), theme);
+        }
+        // サイズを変えない場合はoverlayの確認と同じになる
+        // 上書きした辺が余白に対して小さい場合はその辺を、そうでなければoverlayの余白を報告する
+        if rule.width.is_some() || rule.height.is_some() {
+            let width_path = rule.width.map_or("overlay.padding".to_string(), |_| format!("{}.width", path));
+            let height_path = rule.height.map_or("overlay.padding".to_string(), |_| format!("{}.height", path));
+            self.rounded_box(
+                (&width_path, &height_path, "overlay.corner_radius"),
+                (rule.width.unwrap_or(overlay.width), rule.height.unwrap_or(overlay.height)),
+                overlay.padding,
+                overlay.corner_radius,
+            );
+        }
+    }
+
+    /// 幅・高さ（ピクセル、0ではバッファを作れない）
+    fn size(&mut self, path: &str, value: u32) {
+        if value == 0 {
+            self.error(path, "1以上にしてください".to_string());
+        }
+    }
+
+    fn positive(&mut self, path: &str, value: f64) {
+        if !(value.is_finite() && value > 0.0) {
+            self.error(path, format!("0より大きくしてください（{}）", value));
+        }
+    }
+
+    fn non_negative(&mut self, path: &str, value: f64) {
+        if !(value.is_finite() && value >= 0.0) {
+            self.error(path, format!("0以上にしてください（{}）", value));
+        }
+    }
+
+    fn theme(&mut self, path: &str, theme: &Theme) {
+        let colors = [
+            ("background", theme.background),
+            ("box_color", theme.box_color),
+            ("text", theme.text),
+        ];
+        for (name, (r, g, b, a)) in colors {
+            if [r, g, b, a].iter().any(|component| !(0.0..=1.0).contains(component)) {
+                self.error(
+                    &format!("{}.{}", path, name),
+                    format!("各成分は0.0〜1.0にしてください（{:?}）", (r, g, b, a)),
+                );
+            }
+        }
+    }
+
+    /// 外枠から余白の内側に角丸ボックスを描画できるか（paths: 幅・高さが足りない場合と角の半径の報告先）
+    fn rounded_box(&mut self, paths: (&str, &str, &str), (width, height): (u32, u32), padding: f64, corner_radius: f64) {
+        // 値そのものの問題は報告済み
+        if width == 0 || height == 0 || !(padding >= 0.0 && corner_radius >= 0.0) {
+            return;
+        }
+
+        let box_width = f64::from(width) - 2.0 * padding;
+        let box_height = f64::from(height) - 2.0 * padding;
+        if box_width <= 0.0 || box_height <= 0.0 {
+            let message = format!(
+                "余白（{}）の2倍が大きさ（{}x{}）以上のため角丸ボックスを描画できません",
+                padding, width, height
+            );
+            if box_width <= 0.0 {
+                self.error(paths.0, message.clone());
+            }
+            // 同じ報告先には一度だけ報告する
+            if box_height <= 0.0 && !(box_width <= 0.0 && paths.0 == paths.1) {
+                self.error(paths.1, message);
+            }
+        } else if 2.0 * corner_radius > box_width.min(box_height) {
+            self.warning(
+                paths.2,
+                format!(
+                    "角の半径（{}）が角丸ボックス（{}x{}）の短辺の半分より大きいため形が崩れます",
+                    corner_radius, box_width, box_height
+                ),
+            );
+        }
+    }
+
+    fn font_family(&mut self, path: &str, family: &str) {
+        if family.trim().is_empty() {
+            self.error(path, "フォント名を指定してください".to_string());
+            return;
+        }
+        match fonts::fallback(family) {
+            None => {}
+            Some(fallback) if fallback.is_empty() => {
+                self.warning(path, "fontconfigでフォントが1つも見つかりません".to_string());
+            }
+            Some(fallback) => self.warning(
+                path,
+                format!("「{}」がfontconfigで見つかりません（{}で表示されます）", family, fallback),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(content: &str) -> Config {
+        crate::fonts::use_test_fonts();
+        ron::from_str(content).unwrap()
+    }
+
+    fn problems(config: &Config, severity: Severity) -> Vec<String> {
+        config
+            .validate()
+            .into_iter()
+            .filter(|problem| problem.severity == severity)
+            .map(|problem| problem.path)
+            .collect()
+    }
+
+    #[test]
+    fn built_in_config_has_no_errors() {
+        let config = config(include_str!("../config.ron"));
+        assert_eq!(problems(&config, Severity::Error), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reports_every_error_with_its_path() {
+        let config = config(
+            r#"Config(
+                input_method_names: {},
+                overlay: OverlayConfig(width: 0, height: 20, font_size: 0.0, font_family: "DejaVu Sans",
+                    padding: 15.0, theme: Theme(text: (0.0, 0.0, 0.0, 2.0))),
+                animation: AnimationConfig(display_duration_ms: 500, fade_duration_ms: 200, fade_frames: 0),
+                indicator: IndicatorConfig(badge: BadgeConfig(enlarge_scale: 0.5)),
+                rules: [Rule(height: Some(0)), Rule(width: Some(20))],
+            )"#,
+        );
+        assert_eq!(
+            problems(&config, Severity::Error),
+            [
+                "overlay.width",
+                "overlay.font_size",
+                "overlay.theme.text",
+                "animation.fade_frames",
+                "indicator.badge.enlarge_scale",
+                "rules[0].height",
+                "rules[1].width",
+                "overlay.padding",
+            ]
+        );
+        assert!(config.check().is_err());
+    }
+
+    #[test]
+    fn padding_larger_than_the_box() {
+        let config = config(
+            r#"Config(
+                input_method_names: {},
+                overlay: OverlayConfig(width: 200, height: 30, font_size: 20.0, font_family: "DejaVu Sans", padding: 15.0),
+                animation: AnimationConfig(display_duration_ms: 500, fade_duration_ms: 0, fade_frames: 0),
+            )"#,
+        );
+        assert_eq!(problems(&config, Severity::Error), ["overlay.padding"]);
+    }
+
+    #[test]
+    fn rule_reports_the_side_that_is_too_small() {
+        let config = config(
+            r#"Config(
+                input_method_names: {},
+                overlay: OverlayConfig(width: 200, height: 100, font_size: 20.0, font_family: "DejaVu Sans", padding: 15.0),
+                animation: AnimationConfig(display_duration_ms: 500, fade_duration_ms: 0, fade_frames: 0),
+                rules: [Rule(width: Some(400), height: Some(30)), Rule(width: Some(20), height: Some(20))],
+            )"#,
+        );
+        assert_eq!(
+            problems(&config, Severity::Error),
+            ["rules[0].height", "rules[1].width", "rules[1].height"]
+        );
+    }
+
+    #[test]
+    fn missing_font_is_a_warning() {
+        let config = config(
+            r#"Config(
+                input_method_names: {},
+                overlay: OverlayConfig(width: 200, height: 100, font_size: 48.0, font_family: "No Such Font"),
+                animation: AnimationConfig(display_duration_ms: 500, fade_duration_ms: 200, fade_frames: 10),
+            )"#,
+        );
+        assert_eq!(problems(&config, Severity::Error), Vec::<String>::new());
+        assert_eq!(problems(&config, Severity::Warning), ["overlay.font_family"]);
+        assert!(config.check().is_ok());
+    }
+}
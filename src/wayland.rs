@@ -0,0 +1,386 @@
+// Wayland接続とグローバル、共有メモリバッファの作成
+// オーバーレイとバッジの両方が使う
+
+use std::os::fd::AsFd;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use memmap2::MmapMut;
+use nix::sys::memfd::{memfd_create, MemFdCreateFlag};
+
+// Waylandクライアントライブラリ
+use wayland_client::{
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+    protocol::{wl_compositor, wl_shm, wl_shm_pool, wl_surface, wl_buffer, wl_registry, wl_region, wl_output},
+    globals::{registry_queue_init, GlobalList, GlobalListContents},
+};
+
+// Layer Shellプロトコル
+use wayland_protocols_wlr::layer_shell::v1::client::{
+    zwlr_layer_shell_v1::{self, ZwlrLayerShellV1},
+    zwlr_layer_surface_v1::{self, ZwlrLayerSurfaceV1},
+};
+
+use crate::config::OutputSelection;
+
+/// ピクセルデータからWaylandバッファを作成
+pub fn create_buffer_from_pixels(
+    shm: &wl_shm::WlShm,
+    qh: &QueueHandle<AppState>,
+    width: i32,
+    height: i32,
+    pixels: &[u8],
+) -> Result<wl_buffer::WlBuffer> {
+    let stride = width * 4;
+    let size = stride * height;
+
+    // memfd_create: ディスクI/Oなしの匿名メモリファイル（5-15ms → 1-2ms）
+    let fd = memfd_create(c"wl_shm", MemFdCreateFlag::MFD_CLOEXEC)
+        .context("memfd_createに失敗")?;
+    nix::unistd::ftruncate(&fd, size as i64)
+        .context("ファイルサイズの設定に失敗")?;
+
+    let mut mmap = unsafe {
+        MmapMut::map_mut(&fd)
+            .context("メモリマップに失敗")?
+    };
+    mmap.copy_from_slice(pixels);
+
+    let pool = shm.create_pool(fd.as_fd(), size, qh, ());
+    let buffer = pool.create_buffer(
+        0,
+        width,
+        height,
+        stride,
+        wl_shm::Format::Argb8888,
+        qh,
+        (),
+    );
+    pool.destroy();
+
+    Ok(buffer)
+}
+
+/// 表示スレッドが保持するWayland接続とグローバル
+pub struct WaylandContext {
+    pub conn: Connection,
+    pub event_queue: EventQueue<AppState>,
+    pub qh: QueueHandle<AppState>,
+    globals: GlobalList,
+    pub compositor: wl_compositor::WlCompositor,
+    pub shm: wl_shm::WlShm,
+    pub layer_shell: ZwlrLayerShellV1,
+    /// バインド済みの出力
+    outputs: Vec<Output>,
+}
+
+/// バインド済みの出力
+struct Output {
+    /// レジストリ上のグローバル名
+    global_name: u32,
+    output: wl_output::WlOutput,
+    info: Arc<Mutex<OutputInfo>>,
+}
+
+/// wl_outputのイベントで受け取る出力情報
+#[derive(Default)]
+struct OutputInfo {
+    name: Option<String>,
+    scale: i32,
+}
+
+/// オーバーレイを置く出力（outputがNoneの場合はコンポジタに任せる）
+pub struct OutputTarget {
+    pub output: Option<wl_output::WlOutput>,
+    pub name: Option<String>,
+    pub scale: i32,
+}
+
+/// コンポジタがレイヤーシェルに対応していない（GNOME/Mutterなど）
+#[derive(Debug)]
+pub struct LayerShellUnavailable;
+
+impl std::fmt::Display for LayerShellUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "コンポジタがzwlr_layer_shell_v1に対応していません")
+    }
+}
+
+impl std::error::Error for LayerShellUnavailable {}
+
+impl WaylandContext {
+    /// Waylandコンポジタへ接続し、必要なグローバルをバインド
+    pub fn connect() -> Result<Self> {
+        // Waylandコンポジタへの接続（1回だけ）
+        let conn = Connection::connect_to_env()
+            .context("Waylandコンポジタへの接続に失敗")?;
+        Self::from_connection(conn)
+    }
+
+    /// 確立済みの接続で必要なグローバルをバインド
+    pub fn from_connection(conn: Connection) -> Result<Self> {
+        // イベントキューとグローバルの初期化（1回だけ）
+        let (globals, event_queue) = registry_queue_init::<AppState>(&conn)
+            .context("グローバルレジストリの取得に失敗")?;
+
+        let qh = event_queue.handle();
+
+        // 必要なグローバルをバインド（1回だけ）
+        let compositor: wl_compositor::WlCompositor = globals
+            .bind(&qh, 4..=6, ())
+            .context("wl_compositorのバインドに失敗")?;
+
+        let shm: wl_shm::WlShm = globals
+            .bind(&qh, 1..=1, ())
+            .context("wl_shmのバインドに失敗")?;
+
+        let layer_shell: ZwlrLayerShellV1 = globals
+            .bind(&qh, 1..=4, ())
+            .map_err(|_| LayerShellUnavailable)?;
+
+        let mut wayland = Self {
+            conn,
+            event_queue,
+            qh,
+            globals,
+            compositor,
+            shm,
+            layer_shell,
+            outputs: Vec::new(),
+        };
+        wayland.sync_outputs()?;
+
+        Ok(wayland)
+    }
+
+    /// コンポジタとの接続が生きているか確認（プロトコルエラー・切断時はエラー）
+    pub fn check_connection(&mut self) -> Result<()> {
+        if let Some(error) = self.conn.protocol_error() {
+            anyhow::bail!("Waylandのプロトコルエラー: {}", error);
+        }
+        let mut state = AppState::new();
+        self.event_queue
+            .roundtrip(&mut state)
+            .context("Waylandコンポジタとの接続が切断されました")?;
+        Ok(())
+    }
+
+    /// レジストリの出力一覧とバインド済みの出力を同期（ホットプラグ対応）
+    pub fn sync_outputs(&mut self) -> Result<()> {
+        let available: Vec<(u32, u32)> = self.globals.contents().with_list(|list| {
+            list.iter()
+                .filter(|global| global.interface == "wl_output")
+                .map(|global| (global.name, global.version))
+                .collect()
+        });
+
+        // 取り外された出力を解放
+        self.outputs.retain(|output| {
+            let present = available.iter().any(|(name, _)| *name == output.global_name);
+            if !present && output.output.version() >= 3 {
+                output.output.release();
+            }
+            present
+        });
+
+        // 新しい出力をバインド（nameイベントはバージョン4から）
+        let mut bound = false;
+        for (global_name, version) in available {
+            if self.outputs.iter().any(|output| output.global_name == global_name) {
+                continue;
+            }
+            let info = Arc::new(Mutex::new(OutputInfo::default()));
+            let output = self.globals.registry().bind::<wl_output::WlOutput, _, _>(
+                global_name,
+                version.min(4),
+                &self.qh,
+                Arc::clone(&info),
+            );
+            self.outputs.push(Output { global_name, output, info });
+            bound = true;
+        }
+
+        // 出力情報（名前・スケール）を受け取る
+        if bound {
+            let mut state = AppState::new();
+            self.event_queue.roundtrip(&mut state)?;
+        }
+
+        Ok(())
+    }
+
+    /// 設定に従ってオーバーレイを置く出力を選択
+    pub fn target_outputs(&self, selection: &OutputSelection) -> Vec<OutputTarget> {
+        let selected: Vec<OutputTarget> = self
+            .outputs
+            .iter()
+            .filter_map(|output| {
+                let info = output.info.lock().unwrap();
+                let matched = match selection {
+                    OutputSelection::Focused => false,
+                    OutputSelection::All => true,
+                    OutputSelection::Named(names) => {
+                        info.name.as_ref().is_some_and(|name| names.contains(name))
+                    }
+                };
+                matched.then(|| OutputTarget {
+                    output: Some(output.output.clone()),
+                    name: info.name.clone(),
+                    scale: info.scale.max(1),
+                })
+            })
+            .collect();
+
+        // 該当する出力が無い場合はコンポジタに任せる
+        if selected.is_empty() {
+            vec![OutputTarget {
+                output: None,
+                name: None,
+                scale: 1,
+            }]
+        } else {
+            selected
+        }
+    }
+}
+
+// アプリケーション状態（イベントハンドラ用）
+pub struct AppState {
+    /// configure（またはclosed）を受け取ったレイヤーサーフェスの数
+    pub configured: usize,
+}
+
+impl AppState {
+    pub fn new() -> Self {
+        Self { configured: 0 }
+    }
+}
+
+// Waylandイベントディスパッチャの実装
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for AppState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wl_registry::WlRegistry,
+        _event: wl_registry::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {}
+}
+
+impl Dispatch<wl_compositor::WlCompositor, ()> for AppState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wl_compositor::WlCompositor,
+        _event: wl_compositor::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {}
+}
+
+impl Dispatch<wl_surface::WlSurface, ()> for AppState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wl_surface::WlSurface,
+        _event: wl_surface::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {}
+}
+
+impl Dispatch<wl_shm::WlShm, ()> for AppState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wl_shm::WlShm,
+        _event: wl_shm::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {}
+}
+
+impl Dispatch<wl_shm_pool::WlShmPool, ()> for AppState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wl_shm_pool::WlShmPool,
+        _event: wl_shm_pool::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {}
+}
+
+impl Dispatch<wl_output::WlOutput, Arc<Mutex<OutputInfo>>> for AppState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wl_output::WlOutput,
+        event: wl_output::Event,
+        data: &Arc<Mutex<OutputInfo>>,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        let mut info = data.lock().unwrap();
+        match event {
+            wl_output::Event::Name { name } => info.name = Some(name),
+            wl_output::Event::Scale { factor } => info.scale = factor,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<wl_buffer::WlBuffer, ()> for AppState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wl_buffer::WlBuffer,
+        _event: wl_buffer::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {}
+}
+
+impl Dispatch<wl_region::WlRegion, ()> for AppState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wl_region::WlRegion,
+        _event: wl_region::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {}
+}
+
+impl Dispatch<ZwlrLayerShellV1, ()> for AppState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwlrLayerShellV1,
+        _event: zwlr_layer_shell_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {}
+}
+
+impl Dispatch<ZwlrLayerSurfaceV1, ()> for AppState {
+    fn event(
+        state: &mut Self,
+        _proxy: &ZwlrLayerSurfaceV1,
+        event: zwlr_layer_surface_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_layer_surface_v1::Event::Configure { serial, .. } => {
+                _proxy.ack_configure(serial);
+                state.configured += 1;
+            }
+            zwlr_layer_surface_v1::Event::Closed => {
+                state.configured += 1;
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,100 @@
+// 結合テストの共通部分（専用のセッションバスとテスト対象のデーモン）
+
+use std::ffi::OsStr;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+
+use dbus::blocking::Connection as DbusConnection;
+use dbus::channel::Channel;
+
+/// 専用のセッションバスを起動（dbus-daemonが無い環境ではテストをスキップ）
+macro_rules! private_bus {
+    () => {
+        match common::PrivateBus::start() {
+            Some(bus) => bus,
+            None => {
+                eprintln!("dbus-daemonが無いためスキップします");
+                return;
+            }
+        }
+    };
+}
+
+/// 専用のセッションバス（終了時にdbus-daemonを止める）
+pub struct PrivateBus {
+    daemon: Child,
+    pub address: String,
+}
+
+impl PrivateBus {
+    /// dbus-daemonが無い環境ではNone
+    pub fn start() -> Option<Self> {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        Some(Self {
+            daemon,
+            address: address.trim().to_string(),
+        })
+    }
+
+    pub fn connect(&self) -> DbusConnection {
+        let mut channel = Channel::open_private(&self.address).unwrap();
+        channel.register().unwrap();
+        DbusConnection::from(channel)
+    }
+}
+
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        self.daemon.kill().ok();
+        self.daemon.wait().ok();
+    }
+}
+
+/// テスト対象のデーモン（終了時に止める）
+pub struct Daemon {
+    child: Child,
+    config_home: PathBuf,
+}
+
+impl Daemon {
+    /// 設定ファイルを書いた一時ディレクトリをXDG_CONFIG_HOMEにして起動
+    pub fn start(bus: &PrivateBus, name: &str, config: &str, envs: &[(&str, &OsStr)]) -> Self {
+        let config_home = std::env::temp_dir().join(format!("wayland_fcitx5_indicator-{}-{}", name, std::process::id()));
+        let config_dir = config_home.join("wayland_fcitx5_indicator");
+        std::fs::create_dir_all(&config_dir).unwrap();
+        std::fs::write(config_dir.join("config.ron"), config).unwrap();
+
+        let child = Command::new(env!("CARGO_BIN_EXE_wayland_fcitx5_indicator"))
+            .env("DBUS_SESSION_BUS_ADDRESS", &bus.address)
+            .env("XDG_CONFIG_HOME", &config_home)
+            .env_remove("HYPRLAND_INSTANCE_SIGNATURE")
+            .env_remove("NOTIFY_SOCKET")
+            .env_remove("RUST_LOG")
+            .env_remove("WAYLAND_DISPLAY")
+            .env_remove("WAYLAND_SOCKET")
+            .envs(envs.iter().copied())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        Self { child, config_home }
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+        std::fs::remove_dir_all(&self.config_home).ok();
+    }
+}
@@ -0,0 +1,237 @@
+// fcitx5の偽物を使った入力メソッド監視の結合テスト
+// 専用のdbus-daemonに偽のorg.fcitx.Fcitx5とorg.freedesktop.Notificationsを登録し、
+// デーモン（表示はデスクトップ通知）が送る表示リクエストの順序を確認する
+
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use dbus::arg::PropMap;
+use dbus::blocking::Connection as DbusConnection;
+use dbus::channel::{MatchingReceiver, Sender as _};
+use dbus::message::MatchRule;
+use dbus::Message;
+use dbus_crossroads::{Crossroads, IfaceBuilder};
+
+#[macro_use]
+mod common;
+use common::{Daemon, PrivateBus};
+
+const FCITX5_NAME: &str = "org.fcitx.Fcitx5";
+const CONTROLLER_INTERFACE: &str = "org.fcitx.Fcitx.Controller1";
+const NOTIFICATIONS_NAME: &str = "org.freedesktop.Notifications";
+
+/// 表示リクエストを待つ時間（デーモンのポーリング間隔より十分長く）
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// 表示リクエストが来ないことを確かめる時間
+const QUIET_PERIOD: Duration = Duration::from_millis(1500);
+
+const CONFIG: &str = r#"Config(
+    input_method_names: {
+        "keyboard-us": "en",
+        "mozc": "あ",
+    },
+    overlay: OverlayConfig(width: 200, height: 100, font_size: 48.0, font_family: "sans-serif"),
+    animation: AnimationConfig(display_duration_ms: 100, fade_duration_ms: 0, fade_frames: 0),
+    indicator: IndicatorConfig(backend: Notification),
+    logging: LoggingConfig(level: Off),
+)"#;
+
+enum FakeCommand {
+    /// 入力メソッドを変えてPropertiesChangedを送る
+    Switch(String),
+    /// 入力メソッドは変えずにPropertiesChangedを送る
+    Notify,
+    /// 名前を手放して終了（fcitx5の終了）
+    Stop,
+}
+
+/// 偽のfcitx5（/controllerのCurrentInputMethodとPropertiesChangedのみ）
+struct FakeFcitx5 {
+    commands: Sender<FakeCommand>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FakeFcitx5 {
+    fn start(bus: &PrivateBus, input_method: &str) -> Self {
+        let conn = bus.connect();
+        conn.request_name(FCITX5_NAME, false, true, true).unwrap();
+
+        let current = Arc::new(Mutex::new(input_method.to_string()));
+        let mut cr = Crossroads::new();
+        let iface = cr.register(CONTROLLER_INTERFACE, |b: &mut IfaceBuilder<Arc<Mutex<String>>>| {
+            b.method("CurrentInputMethod", (), ("im",), |_, current, ()| {
+                Ok((current.lock().unwrap().clone(),))
+            });
+        });
+        cr.insert("/controller", &[iface], current.clone());
+        conn.start_receive(
+            MatchRule::new_method_call(),
+            Box::new(move |msg, conn| {
+                cr.handle_message(msg, conn).ok();
+                true
+            }),
+        );
+
+        let (commands, command_rx) = unbounded();
+        let thread = std::thread::spawn(move || loop {
+            conn.process(Duration::from_millis(20)).unwrap();
+            match command_rx.try_recv() {
+                Ok(FakeCommand::Switch(input_method)) => {
+                    *current.lock().unwrap() = input_method;
+                    emit_properties_changed(&conn);
+                }
+                Ok(FakeCommand::Notify) => emit_properties_changed(&conn),
+                Ok(FakeCommand::Stop) => return,
+                Err(_) => {}
+            }
+        });
+
+        Self {
+            commands,
+            thread: Some(thread),
+        }
+    }
+
+    fn switch(&self, input_method: &str) {
+        self.commands.send(FakeCommand::Switch(input_method.to_string())).unwrap();
+    }
+
+    fn notify(&self) {
+        self.commands.send(FakeCommand::Notify).unwrap();
+    }
+}
+
+impl Drop for FakeFcitx5 {
+    fn drop(&mut self) {
+        self.commands.send(FakeCommand::Stop).ok();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+fn emit_properties_changed(conn: &DbusConnection) {
+    let signal = Message::signal(
+        &"/controller".into(),
+        &"org.freedesktop.DBus.Properties".into(),
+        &"PropertiesChanged".into(),
+    )
+    .append3(CONTROLLER_INTERFACE, PropMap::new(), Vec::<String>::new());
+    conn.send(signal).unwrap();
+}
+
+/// 偽の通知サーバ（Notifyのsummary＝表示テキストを受け取った順に渡す）
+fn start_fake_notifications(bus: &PrivateBus) -> Receiver<String> {
+    let conn = bus.connect();
+    conn.request_name(NOTIFICATIONS_NAME, false, true, true).unwrap();
+
+    let (tx, rx) = unbounded();
+    let mut cr = Crossroads::new();
+    let iface = cr.register(NOTIFICATIONS_NAME, |b: &mut IfaceBuilder<Sender<String>>| {
+        b.method(
+            "Notify",
+            ("app_name", "replaces_id", "app_icon", "summary", "body", "actions", "hints", "expire_timeout"),
+            ("id",),
+            |_, tx, (_, _, _, summary, _, _, _, _): (String, u32, String, String, String, Vec<String>, PropMap, i32)| {
+                tx.send(summary).ok();
+                Ok((1u32,))
+            },
+        );
+    });
+    cr.insert("/org/freedesktop/Notifications", &[iface], tx);
+    conn.start_receive(
+        MatchRule::new_method_call(),
+        Box::new(move |msg, conn| {
+            cr.handle_message(msg, conn).ok();
+            true
+        }),
+    );
+
+    // テストの終了時にバスごと止まる
+    std::thread::spawn(move || while conn.process(Duration::from_millis(100)).is_ok() {});
+    rx
+}
+
+fn expect_request(requests: &Receiver<String>, expected: &str) {
+    match requests.recv_timeout(REQUEST_TIMEOUT) {
+        Ok(text) => assert_eq!(text, expected),
+        Err(_) => panic!("表示リクエスト「{}」が届きません", expected),
+    }
+}
+
+fn expect_no_request(requests: &Receiver<String>) {
+    match requests.recv_timeout(QUIET_PERIOD) {
+        Ok(text) => panic!("想定外の表示リクエスト: {}", text),
+        Err(RecvTimeoutError::Timeout) => {}
+        Err(RecvTimeoutError::Disconnected) => panic!("通知サーバが終了しました"),
+    }
+}
+
+#[test]
+fn shows_initial_input_method() {
+    let bus = private_bus!();
+    let _fcitx5 = FakeFcitx5::start(&bus, "mozc");
+    let requests = start_fake_notifications(&bus);
+    let _daemon = Daemon::start(&bus, "initial", CONFIG, &[]);
+
+    expect_request(&requests, "あ");
+    expect_no_request(&requests);
+}
+
+#[test]
+fn shows_each_change_once() {
+    let bus = private_bus!();
+    let fcitx5 = FakeFcitx5::start(&bus, "keyboard-us");
+    let requests = start_fake_notifications(&bus);
+    let _daemon = Daemon::start(&bus, "changes", CONFIG, &[]);
+    expect_request(&requests, "en");
+
+    fcitx5.switch("mozc");
+    expect_request(&requests, "あ");
+
+    // 入力メソッドが変わらないシグナルでは表示しない
+    fcitx5.notify();
+    fcitx5.notify();
+    expect_no_request(&requests);
+
+    fcitx5.switch("keyboard-us");
+    expect_request(&requests, "en");
+    expect_no_request(&requests);
+}
+
+#[test]
+fn follows_fcitx5_restart() {
+    let bus = private_bus!();
+    let fcitx5 = FakeFcitx5::start(&bus, "keyboard-us");
+    let requests = start_fake_notifications(&bus);
+    let _daemon = Daemon::start(&bus, "restart", CONFIG, &[]);
+    expect_request(&requests, "en");
+
+    // fcitx5が終了している間は何も表示しない
+    drop(fcitx5);
+    expect_no_request(&requests);
+
+    // 再起動後の入力メソッドが変わっていれば表示する
+    let fcitx5 = FakeFcitx5::start(&bus, "mozc");
+    expect_request(&requests, "あ");
+
+    // 再起動の前後で同じ入力メソッドなら表示しない
+    drop(fcitx5);
+    let fcitx5 = FakeFcitx5::start(&bus, "mozc");
+    fcitx5.notify();
+    expect_no_request(&requests);
+}
+
+#[test]
+fn starts_before_fcitx5() {
+    let bus = private_bus!();
+    let requests = start_fake_notifications(&bus);
+    let _daemon = Daemon::start(&bus, "late", CONFIG, &[]);
+    expect_no_request(&requests);
+
+    // 後から起動したfcitx5の入力メソッドを表示する
+    let _fcitx5 = FakeFcitx5::start(&bus, "mozc");
+    expect_request(&requests, "あ");
+}
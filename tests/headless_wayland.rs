@@ -0,0 +1,691 @@
+// ヘッドレスのWaylandコンポジタを使ったオーバーレイ表示の結合テスト
+// wayland-serverで最小限のコンポジタ（wl_compositor、wl_shm、wl_output、zwlr_layer_shell_v1）を動かし、
+// デーモンにDBusで表示させたとき（とライブラリのOverlayで表示したとき）の
+// レイヤーサーフェスの設定・バッファのコミット・破棄を確認する
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use wayland_protocols_wlr::layer_shell::v1::server::{
+    zwlr_layer_shell_v1::{self, Layer, ZwlrLayerShellV1},
+    zwlr_layer_surface_v1::{self, Anchor, KeyboardInteractivity, ZwlrLayerSurfaceV1},
+};
+use wayland_server::backend::ClientData;
+use wayland_server::protocol::{
+    wl_buffer::{self, WlBuffer},
+    wl_callback::WlCallback,
+    wl_compositor::{self, WlCompositor},
+    wl_output::{self, WlOutput},
+    wl_region::{self, WlRegion},
+    wl_shm::{self, WlShm},
+    wl_shm_pool::{self, WlShmPool},
+    wl_surface::{self, WlSurface},
+};
+use wayland_fcitx5_indicator::{Config, Overlay};
+use wayland_server::{Client, DataInit, Dispatch, Display, DisplayHandle, GlobalDispatch, ListeningSocket, New, Resource};
+
+#[macro_use]
+mod common;
+use common::{Daemon, PrivateBus};
+
+/// サーフェスの作成から破棄までを待つ時間
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+const DAEMON_NAME: &str = "org.waylandfcitx5indicator.Daemon";
+const DAEMON_PATH: &str = "/org/waylandfcitx5indicator/Daemon";
+
+/// 出力のアンカー・マージンを確認できるようOutputモードで端に置く
+/// （Hyprlandが無いのでモニタ情報は取れず、レイヤーシェルのアンカーで配置される）
+const CONFIG: &str = r#"Config(
+    input_method_names: {},
+    overlay: OverlayConfig(width: 200, height: 100, font_size: 48.0, font_family: "sans-serif"),
+    animation: AnimationConfig(display_duration_ms: 100, fade_duration_ms: 40, fade_frames: 2),
+    placement: PlacementConfig(mode: Output, anchor: TopRight, margin: 20, offset_x: 5, offset_y: 7, outputs: OUTPUTS),
+    indicator: IndicatorConfig(backend: LayerShell),
+    suppress: SuppressConfig(fullscreen: Show, locked: Show),
+    logging: LoggingConfig(level: Off),
+)"#;
+
+/// コミットされたバッファ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BufferInfo {
+    width: i32,
+    height: i32,
+    stride: i32,
+    format: u32,
+}
+
+/// クライアントが作ったサーフェスについて記録した内容
+#[derive(Debug, Default, Clone)]
+struct SurfaceRecord {
+    layer: Option<Layer>,
+    namespace: Option<String>,
+    output: Option<String>,
+    size: (u32, u32),
+    anchor: Option<Anchor>,
+    /// 上・右・下・左
+    margin: (i32, i32, i32, i32),
+    keyboard_interactivity: Option<KeyboardInteractivity>,
+    exclusive_zone: i32,
+    /// 入力リージョンの矩形の数（未設定ならNone）
+    input_region_rects: Option<u32>,
+    buffer_scale: i32,
+    configured: bool,
+    acked: bool,
+    /// 次のcommitで反映するバッファ（Some(None)はバッファを外す）
+    pending_buffer: Option<Option<BufferInfo>>,
+    committed_buffers: Vec<BufferInfo>,
+    /// バッファがアタッチされている（バッファを外すとマップが解除される）
+    mapped: bool,
+    layer_surface_destroyed: bool,
+    surface_destroyed: bool,
+}
+
+type Records = Arc<Mutex<Vec<SurfaceRecord>>>;
+
+/// コンポジタの状態（記録のみ）
+struct Compositor {
+    records: Records,
+    /// サーフェスの記録のインデックスごとのレイヤーサーフェス（configureの送信用）
+    layer_surfaces: HashMap<usize, ZwlrLayerSurfaceV1>,
+    serial: u32,
+}
+
+struct TestClient;
+
+impl ClientData for TestClient {}
+
+/// 専用スレッドで動くヘッドレスのコンポジタ
+struct HeadlessCompositor {
+    records: Records,
+    runtime_dir: PathBuf,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HeadlessCompositor {
+    /// 出力（名前とスケール）を用意して起動
+    fn start(name: &str, outputs: &[(&str, i32)]) -> Self {
+        let runtime_dir = std::env::temp_dir().join(format!("wayland_fcitx5_indicator-wayland-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&runtime_dir).unwrap();
+        let socket = ListeningSocket::bind_absolute(runtime_dir.join("wayland-test")).unwrap();
+
+        let records = Records::default();
+        let stop = Arc::new(AtomicBool::new(false));
+        let outputs: Vec<(String, i32)> = outputs.iter().map(|(name, scale)| (name.to_string(), *scale)).collect();
+
+        let thread = {
+            let records = records.clone();
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                let mut display = Display::<Compositor>::new().unwrap();
+                let mut handle = display.handle();
+                handle.create_global::<Compositor, WlCompositor, _>(4, ());
+                handle.create_global::<Compositor, WlShm, _>(1, ());
+                handle.create_global::<Compositor, ZwlrLayerShellV1, _>(4, ());
+                for output in outputs {
+                    handle.create_global::<Compositor, WlOutput, _>(4, output);
+                }
+
+                let mut state = Compositor {
+                    records,
+                    layer_surfaces: HashMap::new(),
+                    serial: 0,
+                };
+                while !stop.load(Ordering::Relaxed) {
+                    if let Some(stream) = socket.accept().unwrap() {
+                        handle.insert_client(stream, Arc::new(TestClient)).unwrap();
+                    }
+                    display.dispatch_clients(&mut state).unwrap();
+                    display.flush_clients().unwrap();
+                    std::thread::sleep(Duration::from_millis(2));
+                }
+            })
+        };
+
+        Self {
+            records,
+            runtime_dir,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// デーモンに渡す環境変数
+    fn envs(&self) -> [(&str, &OsStr); 2] {
+        [
+            ("XDG_RUNTIME_DIR", self.runtime_dir.as_os_str()),
+            ("WAYLAND_DISPLAY", OsStr::new("wayland-test")),
+        ]
+    }
+
+    /// クライアントとして接続
+    fn connect(&self) -> wayland_client::Connection {
+        let stream = UnixStream::connect(self.runtime_dir.join("wayland-test")).unwrap();
+        wayland_client::Connection::from_socket(stream).unwrap()
+    }
+
+    /// 条件を満たすまで記録を確認（満たした時点の記録を返す）
+    fn wait_for(&self, description: &str, condition: impl Fn(&[SurfaceRecord]) -> bool) -> Vec<SurfaceRecord> {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            let records = self.records.lock().unwrap().clone();
+            if condition(&records) {
+                return records;
+            }
+            assert!(Instant::now() < deadline, "{}を待っている間にタイムアウト: {:#?}", description, records);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+impl Drop for HeadlessCompositor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+        std::fs::remove_dir_all(&self.runtime_dir).ok();
+    }
+}
+
+impl GlobalDispatch<WlCompositor, ()> for Compositor {
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<WlCompositor>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<WlCompositor, ()> for Compositor {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &WlCompositor,
+        request: wl_compositor::Request,
+        _data: &(),
+        _handle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            wl_compositor::Request::CreateSurface { id } => {
+                let mut records = state.records.lock().unwrap();
+                records.push(SurfaceRecord {
+                    buffer_scale: 1,
+                    ..Default::default()
+                });
+                data_init.init(id, records.len() - 1);
+            }
+            wl_compositor::Request::CreateRegion { id } => {
+                data_init.init(id, Mutex::new(0u32));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlSurface, usize> for Compositor {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &WlSurface,
+        request: wl_surface::Request,
+        index: &usize,
+        _handle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let mut records = state.records.lock().unwrap();
+        let record = &mut records[*index];
+        match request {
+            wl_surface::Request::Attach { buffer, .. } => {
+                record.pending_buffer = Some(buffer.and_then(|buffer| buffer.data::<BufferInfo>().copied()));
+            }
+            wl_surface::Request::SetInputRegion { region } => {
+                record.input_region_rects = region.map(|region| *region.data::<Mutex<u32>>().unwrap().lock().unwrap());
+            }
+            wl_surface::Request::SetBufferScale { scale } => record.buffer_scale = scale,
+            wl_surface::Request::Frame { callback } => {
+                data_init.init(callback, ()).done(0);
+            }
+            wl_surface::Request::Commit => {
+                match record.pending_buffer.take() {
+                    Some(Some(buffer)) => {
+                        record.committed_buffers.push(buffer);
+                        record.mapped = true;
+                    }
+                    Some(None) => record.mapped = false,
+                    None => {}
+                }
+                // レイヤーサーフェスの最初のcommitには要求されたサイズでconfigureを返す
+                if let Some(layer_surface) = state.layer_surfaces.get(index).filter(|_| !record.configured) {
+                    record.configured = true;
+                    state.serial += 1;
+                    layer_surface.configure(state.serial, record.size.0, record.size.1);
+                }
+            }
+            wl_surface::Request::Destroy => record.surface_destroyed = true,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlCallback, ()> for Compositor {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &WlCallback,
+        _request: <WlCallback as Resource>::Request,
+        _data: &(),
+        _handle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlRegion, Mutex<u32>> for Compositor {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &WlRegion,
+        request: wl_region::Request,
+        rects: &Mutex<u32>,
+        _handle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let wl_region::Request::Add { .. } = request {
+            *rects.lock().unwrap() += 1;
+        }
+    }
+}
+
+impl GlobalDispatch<WlShm, ()> for Compositor {
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<WlShm>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let shm = data_init.init(resource, ());
+        shm.format(wl_shm::Format::Argb8888);
+        shm.format(wl_shm::Format::Xrgb8888);
+    }
+}
+
+impl Dispatch<WlShm, ()> for Compositor {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &WlShm,
+        request: wl_shm::Request,
+        _data: &(),
+        _handle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let wl_shm::Request::CreatePool { id, .. } = request {
+            data_init.init(id, ());
+        }
+    }
+}
+
+impl Dispatch<WlShmPool, ()> for Compositor {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &WlShmPool,
+        request: wl_shm_pool::Request,
+        _data: &(),
+        _handle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let wl_shm_pool::Request::CreateBuffer {
+            id,
+            width,
+            height,
+            stride,
+            format,
+            ..
+        } = request
+        {
+            let format = format.into_result().map(|format| format as u32).unwrap_or(u32::MAX);
+            data_init.init(
+                id,
+                BufferInfo {
+                    width,
+                    height,
+                    stride,
+                    format,
+                },
+            );
+        }
+    }
+}
+
+impl Dispatch<WlBuffer, BufferInfo> for Compositor {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &WlBuffer,
+        _request: wl_buffer::Request,
+        _data: &BufferInfo,
+        _handle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
+
+impl GlobalDispatch<WlOutput, (String, i32)> for Compositor {
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<WlOutput>,
+        (name, scale): &(String, i32),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let output = data_init.init(resource, name.clone());
+        output.geometry(
+            0,
+            0,
+            600,
+            340,
+            wl_output::Subpixel::None,
+            "test".to_string(),
+            name.clone(),
+            wl_output::Transform::Normal,
+        );
+        output.mode(wl_output::Mode::Current, 1920, 1080, 60000);
+        output.scale(*scale);
+        if output.version() >= 4 {
+            output.name(name.clone());
+        }
+        output.done();
+    }
+}
+
+impl Dispatch<WlOutput, String> for Compositor {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &WlOutput,
+        _request: wl_output::Request,
+        _data: &String,
+        _handle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
+
+impl GlobalDispatch<ZwlrLayerShellV1, ()> for Compositor {
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrLayerShellV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<ZwlrLayerShellV1, ()> for Compositor {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ZwlrLayerShellV1,
+        request: zwlr_layer_shell_v1::Request,
+        _data: &(),
+        _handle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let zwlr_layer_shell_v1::Request::GetLayerSurface {
+            id,
+            surface,
+            output,
+            layer,
+            namespace,
+        } = request
+        {
+            let index = *surface.data::<usize>().unwrap();
+            let mut records = state.records.lock().unwrap();
+            let record = &mut records[index];
+            record.layer = layer.into_result().ok();
+            record.namespace = Some(namespace);
+            record.output = output.and_then(|output| output.data::<String>().cloned());
+            let layer_surface = data_init.init(id, index);
+            state.layer_surfaces.insert(index, layer_surface);
+        }
+    }
+}
+
+impl Dispatch<ZwlrLayerSurfaceV1, usize> for Compositor {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ZwlrLayerSurfaceV1,
+        request: zwlr_layer_surface_v1::Request,
+        index: &usize,
+        _handle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        let mut records = state.records.lock().unwrap();
+        let record = &mut records[*index];
+        match request {
+            zwlr_layer_surface_v1::Request::SetSize { width, height } => record.size = (width, height),
+            zwlr_layer_surface_v1::Request::SetAnchor { anchor } => record.anchor = anchor.into_result().ok(),
+            zwlr_layer_surface_v1::Request::SetExclusiveZone { zone } => record.exclusive_zone = zone,
+            zwlr_layer_surface_v1::Request::SetMargin {
+                top,
+                right,
+                bottom,
+                left,
+            } => record.margin = (top, right, bottom, left),
+            zwlr_layer_surface_v1::Request::SetKeyboardInteractivity {
+                keyboard_interactivity,
+            } => record.keyboard_interactivity = keyboard_interactivity.into_result().ok(),
+            zwlr_layer_surface_v1::Request::AckConfigure { .. } => record.acked = true,
+            zwlr_layer_surface_v1::Request::Destroy => {
+                record.layer_surface_destroyed = true;
+                state.layer_surfaces.remove(index);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// デーモンのDBusメソッドでテキストを表示させる（起動直後は名前の取得を待つ）
+fn request_show(bus: &PrivateBus, text: &str) {
+    let conn = bus.connect();
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        let result: Result<(), _> = conn
+            .with_proxy(DAEMON_NAME, DAEMON_PATH, Duration::from_secs(2))
+            .method_call(DAEMON_NAME, "Show", (text,));
+        match result {
+            Ok(()) => return,
+            Err(_) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(50)),
+            Err(e) => panic!("デーモンに表示を依頼できません: {}", e),
+        }
+    }
+}
+
+/// デーモンのDBusメソッドを引数なしで呼ぶ（名前を取得した後に使う）
+fn call_daemon(bus: &PrivateBus, method: &str) {
+    bus.connect()
+        .with_proxy(DAEMON_NAME, DAEMON_PATH, Duration::from_secs(2))
+        .method_call::<(), _, _, _>(DAEMON_NAME, method, ())
+        .unwrap_or_else(|e| panic!("{}を呼べません: {}", method, e));
+}
+
+fn start_daemon(bus: &PrivateBus, compositor: &HeadlessCompositor, name: &str, outputs: &str) -> Daemon {
+    let config = CONFIG.replace("OUTPUTS", outputs);
+    Daemon::start(bus, name, &config, &compositor.envs())
+}
+
+fn overlays(records: &[SurfaceRecord]) -> Vec<&SurfaceRecord> {
+    records
+        .iter()
+        .filter(|record| record.namespace.as_deref() == Some("modal_ime_indicator"))
+        .collect()
+}
+
+fn assert_overlay_surface(overlay: &SurfaceRecord) {
+    assert_eq!(overlay.layer, Some(Layer::Overlay));
+    assert_eq!(overlay.size, (200, 100));
+    assert_eq!(overlay.anchor, Some(Anchor::Top | Anchor::Right));
+    // アンカーした辺だけに設定する（上はmargin + offset_y、右はmargin - offset_x）
+    assert_eq!(overlay.margin, (27, 15, 0, 0));
+    assert_eq!(overlay.keyboard_interactivity, Some(KeyboardInteractivity::None));
+    assert_eq!(overlay.exclusive_zone, -1);
+    // 入力リージョンは空（クリックを下のウィンドウに通す）
+    assert_eq!(overlay.input_region_rects, Some(0));
+    assert!(overlay.acked, "configureに応答していません");
+}
+
+#[test]
+fn overlay_is_configured_committed_and_destroyed() {
+    let bus = private_bus!();
+    let compositor = HeadlessCompositor::start("single", &[("HEADLESS-1", 1)]);
+    let _daemon = start_daemon(&bus, &compositor, "wayland-single", "Focused");
+
+    request_show(&bus, "en");
+    let records = compositor.wait_for("オーバーレイの破棄", |records| {
+        overlays(records)
+            .iter()
+            .any(|overlay| overlay.layer_surface_destroyed && overlay.surface_destroyed)
+    });
+
+    let overlays = overlays(&records);
+    assert_eq!(overlays.len(), 1);
+    let overlay = overlays[0];
+    assert_overlay_surface(overlay);
+    // Focusedではコンポジタに出力を任せる
+    assert_eq!(overlay.output, None);
+    assert_eq!(overlay.buffer_scale, 1);
+
+    // 最初のフレームとフェードアウトの各フレーム
+    let first = BufferInfo {
+        width: 200,
+        height: 100,
+        stride: 800,
+        format: wl_shm::Format::Argb8888 as u32,
+    };
+    assert_eq!(overlay.committed_buffers.len(), 3);
+    assert!(overlay.committed_buffers.iter().all(|buffer| *buffer == first));
+}
+
+#[test]
+fn overlay_on_every_output_with_its_scale() {
+    let bus = private_bus!();
+    let compositor = HeadlessCompositor::start("multi", &[("HEADLESS-1", 1), ("HEADLESS-2", 2)]);
+    let _daemon = start_daemon(&bus, &compositor, "wayland-multi", "All");
+
+    request_show(&bus, "en");
+    let records = compositor.wait_for("全出力のオーバーレイの破棄", |records| {
+        let overlays = overlays(records);
+        overlays.len() == 2 && overlays.iter().all(|overlay| overlay.layer_surface_destroyed && overlay.surface_destroyed)
+    });
+
+    for (name, scale) in [("HEADLESS-1", 1), ("HEADLESS-2", 2)] {
+        let overlay = overlays(&records)
+            .into_iter()
+            .find(|overlay| overlay.output.as_deref() == Some(name))
+            .unwrap_or_else(|| panic!("{}にオーバーレイがありません", name));
+        assert_overlay_surface(overlay);
+        assert_eq!(overlay.buffer_scale, scale);
+
+        // バッファは出力のスケール倍の大きさで描画する
+        let first = overlay.committed_buffers.first().expect("バッファがコミットされていません");
+        assert_eq!((first.width, first.height), (200 * scale, 100 * scale));
+    }
+}
+
+#[test]
+fn library_overlay_shows_on_given_connection() {
+    let compositor = HeadlessCompositor::start("library", &[("HEADLESS-1", 1)]);
+    let config: Config = ron::from_str(&CONFIG.replace("OUTPUTS", "Focused")).unwrap();
+
+    // 呼び出し側の接続を共有して、同じオーバーレイを2回表示する
+    // （Hyprlandを使わず、配置をレイヤーシェルのアンカーに任せる）
+    let mut overlay = Overlay::from_connection(compositor.connect(), config, None).unwrap();
+    overlay.show("あ").unwrap();
+    overlay.show("あ").unwrap();
+
+    let records = compositor.wait_for("2つのオーバーレイの破棄", |records| {
+        let overlays = overlays(records);
+        overlays.len() == 2 && overlays.iter().all(|overlay| overlay.layer_surface_destroyed && overlay.surface_destroyed)
+    });
+    for overlay in overlays(&records) {
+        assert_overlay_surface(overlay);
+        assert_eq!(overlay.committed_buffers.len(), 3);
+    }
+}
+
+#[test]
+fn badge_on_selected_output_with_its_scale() {
+    let bus = private_bus!();
+    let compositor = HeadlessCompositor::start("badge", &[("HEADLESS-1", 1), ("HEADLESS-2", 2)]);
+    let config = CONFIG
+        .replace("OUTPUTS", r#"Named(["HEADLESS-2"])"#)
+        .replace("IndicatorConfig(", "IndicatorConfig(mode: Persistent, ");
+    let _daemon = Daemon::start(&bus, "wayland-badge", &config, &compositor.envs());
+
+    request_show(&bus, "en");
+    let records = compositor.wait_for("バッジのコミット", |records| {
+        records.iter().any(|record| {
+            record.namespace.as_deref() == Some("modal_ime_indicator_badge") && !record.committed_buffers.is_empty()
+        })
+    });
+
+    let badge = records
+        .iter()
+        .find(|record| record.namespace.as_deref() == Some("modal_ime_indicator_badge"))
+        .unwrap();
+    assert_eq!(badge.layer, Some(Layer::Top));
+    assert_eq!(badge.output.as_deref(), Some("HEADLESS-2"));
+    assert_eq!(badge.size, (64, 40));
+    assert_eq!(badge.buffer_scale, 2);
+    let first = badge.committed_buffers[0];
+    assert_eq!((first.width, first.height), (64 * 2, 40 * 2));
+}
+
+#[test]
+fn badge_is_hidden_while_paused() {
+    let bus = private_bus!();
+    let compositor = HeadlessCompositor::start("badge-pause", &[("HEADLESS-1", 1)]);
+    let config = CONFIG
+        .replace("OUTPUTS", "Focused")
+        .replace("IndicatorConfig(", "IndicatorConfig(mode: Persistent, ");
+    let _daemon = Daemon::start(&bus, "wayland-badge-pause", &config, &compositor.envs());
+    let badge = |records: &[SurfaceRecord]| {
+        records
+            .iter()
+            .find(|record| record.namespace.as_deref() == Some("modal_ime_indicator_badge"))
+            .cloned()
+    };
+
+    request_show(&bus, "en");
+    compositor.wait_for("バッジの表示", |records| badge(records).is_some_and(|badge| badge.mapped));
+
+    // 一時停止中はバッファを外し、再開すると表示し直す
+    call_daemon(&bus, "Pause");
+    compositor.wait_for("バッジを隠す", |records| badge(records).is_some_and(|badge| !badge.mapped));
+    call_daemon(&bus, "Resume");
+    let records = compositor.wait_for("バッジの再表示", |records| {
+        badge(records).is_some_and(|badge| badge.mapped && badge.committed_buffers.len() == 2)
+    });
+    assert!(!badge(&records).unwrap().surface_destroyed);
+}